@@ -0,0 +1,318 @@
+//! Derive macros for `configurable`
+//!
+//! These are re-exported by `configurable` with its `derive` feature
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
+
+/// Derives `configurable::Merge`, merging each field of a struct
+///
+/// Fields are merged with their own `Merge`, unless they have one of these:
+/// * `#[merge(replace)]`: the later value replaces it
+/// * `#[merge(append)]`: the later values are appended to it (e.g. for a `Vec`)
+/// * `#[merge(with = "path")]`: it is merged with `fn(&mut T, T)` at `path`
+///
+/// An enum is replaced by the later value. Each type parameter has to be `Merge`.
+/// For `Merge::merge_value`, the fields are found by their serde names (with
+/// `#[serde(rename = "..")]` and `#[serde(rename_all = "..")]`)
+#[proc_macro_derive(Merge, attributes(merge))]
+pub fn derive_merge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    merge(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `configurable::Configurable`
+///
+/// The type is described with `#[configurable(..)]`:
+/// * `org = ".."`, `app = ".."` and `name = ".."`: the `ORGANIZATION`, `APPLICATION`
+///   and `NAME`, which are required
+/// * `kind = ".."`: `"config"` (the default) or `"data"`, for the `LOCATION`, or
+///   `"system"` for `SystemConfig` (which is also implemented)
+/// * `qualifier = ".."`: the `QUALIFIER`
+/// * `format = ".."`: the `Format`, which defaults to `Detect<Self>`
+#[proc_macro_derive(Configurable, attributes(configurable))]
+pub fn derive_configurable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    configurable(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn configurable(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let (mut org, mut app, mut file, mut qualifier) = (None, None, None, None);
+    let (mut kind, mut format) = (None, None);
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("configurable"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let slot = if meta.path.is_ident("org") {
+                &mut org
+            } else if meta.path.is_ident("app") {
+                &mut app
+            } else if meta.path.is_ident("name") {
+                &mut file
+            } else if meta.path.is_ident("qualifier") {
+                &mut qualifier
+            } else if meta.path.is_ident("kind") {
+                &mut kind
+            } else if meta.path.is_ident("format") {
+                &mut format
+            } else {
+                let msg = "expected `org`, `app`, `name`, `kind`, `qualifier` or `format`";
+                return Err(meta.error(msg));
+            };
+            *slot = Some(meta.value()?.parse::<syn::LitStr>()?);
+            Ok(())
+        })?;
+    }
+
+    let required = |value: Option<syn::LitStr>, key: &str| {
+        value.ok_or_else(|| {
+            let msg = format!("missing `#[configurable({} = \"..\")]`", key);
+            syn::Error::new_spanned(name, msg)
+        })
+    };
+    let (org, app, file) = (
+        required(org, "org")?,
+        required(app, "app")?,
+        required(file, "name")?,
+    );
+    let qualifier = qualifier.map(|qualifier| quote!(const QUALIFIER: &'static str = #qualifier;));
+    let format = match format {
+        Some(format) => format.parse::<syn::Type>()?,
+        None => syn::parse_quote!(::configurable::Detect<Self>),
+    };
+    let location = |location: TokenStream2| {
+        let location = quote!(::configurable::Location::#location);
+        quote!(const LOCATION: ::configurable::Location = #location;)
+    };
+    // `SystemConfig` is delegated to, for its `WRITABLE`
+    let (members, system) = match kind.as_ref().map(syn::LitStr::value).as_deref() {
+        None | Some("config") => (location(quote!(Config)), None),
+        Some("data") => (location(quote!(Data)), None),
+        Some("system") => {
+            let delegate = quote! {
+                fn try_dir() -> ::std::result::Result<::std::path::PathBuf, ::configurable::Error> {
+                    <Self as ::configurable::SystemConfig>::try_dir()
+                }
+
+                fn ensure_dir() -> ::std::result::Result<::std::path::PathBuf, ::configurable::Error> {
+                    <Self as ::configurable::SystemConfig>::ensure_dir()
+                }
+            };
+            let system = quote! {
+                impl #impl_generics ::configurable::SystemConfig for #name #ty_generics #where_clause {}
+            };
+            (delegate, Some(system))
+        }
+        Some(..) => {
+            let msg = "expected `kind` to be \"config\", \"data\" or \"system\"";
+            return Err(syn::Error::new_spanned(kind, msg));
+        }
+    };
+
+    Ok(quote! {
+        #system
+
+        impl #impl_generics ::configurable::Configurable for #name #ty_generics #where_clause {
+            #qualifier
+            const ORGANIZATION: &'static str = #org;
+            const APPLICATION: &'static str = #app;
+            const NAME: &'static str = #file;
+            type Format = #format;
+
+            #members
+        }
+    })
+}
+
+/// How a field is merged
+enum Strategy {
+    Merge,
+    Replace,
+    Append,
+    With(syn::Path),
+}
+
+fn merge(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    // like serde, each type parameter has to be `Merge`
+    let params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone());
+    let bounds = params
+        .map(|param| syn::parse_quote!(#param: ::configurable::Merge))
+        .collect::<Vec<syn::WherePredicate>>();
+    input.generics.make_where_clause().predicates.extend(bounds);
+
+    let name = &input.ident;
+    let rename_all = rename_all(&input.attrs);
+    let (mut merges, mut values) = (vec![], vec![]);
+    let mut bounds = Vec::<syn::WherePredicate>::new();
+    let (body, value) = match &input.data {
+        Data::Struct(data) => {
+            let fields = match &data.fields {
+                Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+                Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+                Fields::Unit => vec![],
+            };
+            let newtype = fields.len() == 1 && fields[0].ident.is_none();
+            for (index, field) in fields.into_iter().enumerate() {
+                let member = match &field.ident {
+                    Some(ident) => syn::Member::Named(ident.clone()),
+                    None => syn::Member::Unnamed(index.into()),
+                };
+                let (this, other) = (quote!(self.#member), quote!(other.#member));
+                let ty = &field.ty;
+                let strategy = strategy(&field.attrs)?;
+                merges.push(match &strategy {
+                    Strategy::Merge => quote!(::configurable::Merge::merge(&mut #this, #other);),
+                    Strategy::Replace => quote!(#this = #other;),
+                    Strategy::Append => quote!(::std::iter::Extend::extend(&mut #this, #other);),
+                    Strategy::With(path) => quote!(#path(&mut #this, #other);),
+                });
+                let merge_value = match strategy {
+                    Strategy::Merge => quote!(<#ty as ::configurable::Merge>::merge_value),
+                    Strategy::Replace => quote!(::configurable::merge::replace),
+                    Strategy::Append => quote!(::configurable::merge::append),
+                    Strategy::With(path) => {
+                        bounds.push(syn::parse_quote! {
+                            #ty: ::configurable::merge::Serialize + ::configurable::merge::DeserializeOwned
+                        });
+                        quote!(|value, other| ::configurable::merge::with::<#ty>(value, other, #path))
+                    }
+                };
+                if newtype {
+                    values.push(quote!((#merge_value)(value, other)));
+                } else if let Some(ident) = &field.ident {
+                    let key = serde_name(ident, &field.attrs, rename_all.as_deref());
+                    values.push(quote!(#key => (#merge_value)(value, other),));
+                }
+            }
+            let value = match newtype {
+                true => quote!(#(#values)*;),
+                false => quote! {
+                    ::configurable::merge::fields_by_key(value, other, |key, value, other| {
+                        match key {
+                            #(#values)*
+                            _ => ::configurable::merge::deep(value, other),
+                        }
+                    });
+                },
+            };
+            (quote!(#(#merges)*), value)
+        }
+        Data::Enum(..) => (quote!(*self = other;), quote!(*value = other;)),
+        Data::Union(data) => {
+            let msg = "`Merge` cannot be derived for unions";
+            return Err(syn::Error::new_spanned(data.union_token, msg));
+        }
+    };
+
+    input.generics.make_where_clause().predicates.extend(bounds);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::configurable::Merge for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn merge(&mut self, other: Self) {
+                #body
+            }
+
+            fn merge_value(
+                value: &mut ::configurable::merge::Value,
+                other: ::configurable::merge::Value,
+            ) {
+                #value
+            }
+        }
+    })
+}
+
+/// The strategy from the `#[merge(..)]` attributes of a field
+fn strategy(attrs: &[syn::Attribute]) -> syn::Result<Strategy> {
+    let mut strategy = Strategy::Merge;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("merge")) {
+        attr.parse_nested_meta(|meta| {
+            strategy = if meta.path.is_ident("replace") {
+                Strategy::Replace
+            } else if meta.path.is_ident("append") {
+                Strategy::Append
+            } else if meta.path.is_ident("with") {
+                let path: syn::LitStr = meta.value()?.parse()?;
+                Strategy::With(path.parse()?)
+            } else {
+                return Err(meta.error("expected `replace`, `append` or `with`"));
+            };
+            Ok(())
+        })?;
+    }
+    Ok(strategy)
+}
+
+/// The key of a field when it is serialized, from its `#[serde(rename = "..")]` or
+/// the container's `#[serde(rename_all = "..")]`
+fn serde_name(ident: &syn::Ident, attrs: &[syn::Attribute], rename_all: Option<&str>) -> String {
+    use syn::ext::IdentExt as _;
+    let name = ident.unraw().to_string();
+    if let Some(rename) = serde_attr(attrs, "rename") {
+        return rename;
+    }
+    let pascal = || {
+        let words = name.split('_').filter(|word| !word.is_empty());
+        let capitalized = words.map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_uppercase().chain(chars).collect::<String>()
+            })
+        });
+        capitalized.collect::<String>()
+    };
+    match rename_all {
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => name.to_ascii_uppercase(),
+        Some("PascalCase") => pascal(),
+        Some("camelCase") => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_lowercase().chain(chars).collect()
+            })
+        }
+        Some("kebab-case") => name.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => name.to_ascii_uppercase().replace('_', "-"),
+        _ => name,
+    }
+}
+
+/// The `#[serde(rename_all = "..")]` of a container
+fn rename_all(attrs: &[syn::Attribute]) -> Option<String> {
+    serde_attr(attrs, "rename_all")
+}
+
+/// The string value of `#[serde(key = "..")]`, if there is one
+///
+/// The other serde attributes are skipped, as serde checks them
+fn serde_attr(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) && meta.input.peek(syn::Token![=]) {
+                value = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+            Ok(())
+        });
+    }
+    value
+}
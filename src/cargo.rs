@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+
+/// Implements the identity of a `Configurable` from the metadata of the package
+///
+/// This is used inside of `impl Configurable`, and it sets:
+/// * `APPLICATION` to the name of the package (`CARGO_PKG_NAME`)
+/// * `ORGANIZATION` to the owner in the `repository` of the package (e.g. `museun`
+///   in `https://github.com/museun/foo`), or else its first author (without the email)
+/// * `qualifier` to the reversed host of the `repository` (e.g. `com.github`), or
+///   else `QUALIFIER`
+///
+/// The values are from the package which uses the macro, when it is compiled
+///
+/// ```
+/// # use configurable::{Configurable, TomlFormat};
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct Foo { port: u16 }
+///
+/// impl Configurable for Foo {
+///     configurable::from_cargo!();
+///     const NAME: &'static str = "config.toml";
+///     type Format = TomlFormat;
+/// }
+///
+/// // for this package, the repository is `https://github.com/museun/configurable`
+/// assert_eq!(Foo::APPLICATION, "configurable");
+/// assert_eq!(Foo::ORGANIZATION, "museun");
+/// assert_eq!(Foo::qualifier(), "com.github");
+/// ```
+#[macro_export]
+macro_rules! from_cargo {
+    () => {
+        const ORGANIZATION: &'static str = $crate::cargo::organization(
+            ::std::env!("CARGO_PKG_REPOSITORY"),
+            ::std::env!("CARGO_PKG_AUTHORS"),
+        );
+        const APPLICATION: &'static str = ::std::env!("CARGO_PKG_NAME");
+
+        fn qualifier() -> ::std::borrow::Cow<'static, str> {
+            $crate::cargo::qualifier(::std::env!("CARGO_PKG_REPOSITORY"), Self::QUALIFIER)
+        }
+    };
+}
+
+/// The owner in the `repository`, or else the first of the `authors`
+pub const fn organization<'a>(repository: &'a str, authors: &'a str) -> &'a str {
+    let owner = owner(repository);
+    if !owner.is_empty() {
+        return owner;
+    }
+    // e.g. `museun <museun@outlook.com>:someone else`
+    let bytes = authors.as_bytes();
+    let mut end = 0;
+    while end < bytes.len() && bytes[end] != b':' && bytes[end] != b'<' {
+        end += 1;
+    }
+    while end > 0 && bytes[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    slice(authors, 0, end)
+}
+
+/// The reversed host of the `repository`, or else `default`
+pub fn qualifier(repository: &str, default: &'static str) -> Cow<'static, str> {
+    match host(repository) {
+        "" => Cow::Borrowed(default),
+        host => Cow::Owned(host.rsplit('.').collect::<Vec<_>>().join(".")),
+    }
+}
+
+/// The host of an url, e.g. `github.com` in `https://github.com/museun/foo`
+const fn host(url: &str) -> &str {
+    let (start, end) = host_range(url);
+    slice(url, start, end)
+}
+
+/// The first segment of the path of an url, e.g. `museun` in `https://github.com/museun/foo`
+const fn owner(url: &str) -> &str {
+    let (_, host) = host_range(url);
+    let bytes = url.as_bytes();
+    if host >= bytes.len() {
+        return "";
+    }
+    // skip the `/` after the host
+    let start = host + 1;
+    let mut end = start;
+    while end < bytes.len() && bytes[end] != b'/' {
+        end += 1;
+    }
+    slice(url, start, end)
+}
+
+/// Where the host of an url starts and ends
+const fn host_range(url: &str) -> (usize, usize) {
+    let bytes = url.as_bytes();
+    let mut start = 0;
+    let mut i = 0;
+    while i + 3 <= bytes.len() {
+        if bytes[i] == b':' && bytes[i + 1] == b'/' && bytes[i + 2] == b'/' {
+            start = i + 3;
+            break;
+        }
+        i += 1;
+    }
+    let mut end = start;
+    while end < bytes.len() && bytes[end] != b'/' {
+        end += 1;
+    }
+    (start, end)
+}
+
+/// The part of `s` between the ascii characters at `start` and `end`
+const fn slice(s: &str, start: usize, end: usize) -> &str {
+    let (head, _) = s.as_bytes().split_at(end);
+    let (_, part) = head.split_at(start);
+    match std::str::from_utf8(part) {
+        Ok(part) => part,
+        Err(..) => "",
+    }
+}
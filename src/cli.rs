@@ -0,0 +1,117 @@
+use super::*;
+
+use clap::{Arg, ArgAction, ArgMatches};
+
+/// Command-line arguments which mirror a `Configurable`, with the `clap` feature
+///
+/// Each value in the defaults (see `Configurable::load_embedded`) gets a flag, named
+/// after its dotted key with `-`s (e.g. `server.port` is `--server-port`). Any value
+/// can also be set with `--set key=value` (which is how values without a default, or
+/// named `set` or `help`, are set). Values are parsed as the type of the field, like
+/// with `Configurable::apply_overrides`
+///
+/// This is implemented for every `Configurable` type
+///
+/// ```
+/// # use configurable::{CliConfigurable, Configurable, TomlFormat};
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct Foo {
+///     port: u16,
+///     verbose: bool,
+///     server: Server,
+/// }
+///
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct Server {
+///     host: String,
+/// }
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "foobar";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+///
+/// let command = clap::Command::new("foo").args(Foo::args().unwrap());
+/// let matches = command
+///     .try_get_matches_from(vec!["foo", "--port", "8080", "--verbose", "--server-host", "localhost"])
+///     .unwrap();
+///
+/// // `Foo::from_layers(&matches)` would use these over the files and environment
+/// let foo = Foo::default().apply_matches(&matches).unwrap();
+/// assert_eq!(foo.port, 8080);
+/// assert!(foo.verbose);
+/// assert_eq!(foo.server.host, "localhost");
+/// ```
+pub trait CliConfigurable: Configurable {
+    /// The arguments for the values of the configuration, and `--set`
+    ///
+    /// These can be added to a `clap::Command` with `Command::args`
+    fn args() -> Result<Vec<Arg>, Error> {
+        let defaults = toml::Value::try_from(Self::load_embedded()?)?;
+        let mut args = vec![];
+        for key in flags(&defaults) {
+            let name = key.rsplit('.').next().unwrap_or(&key).to_uppercase();
+            let mut arg = Arg::new(key.clone())
+                .long(key.replace(['.', '_'], "-"))
+                .value_name(name)
+                .help(format!("Sets `{}`", key));
+            if let Some(toml::Value::Boolean(..)) = get(&defaults, &key) {
+                arg = arg.num_args(0..=1).default_missing_value("true");
+            }
+            args.push(arg);
+        }
+        args.push(
+            Arg::new(SET)
+                .long(SET)
+                .value_name("KEY=VALUE")
+                .action(ArgAction::Append)
+                .help("Sets the value at a dotted key"),
+        );
+        Ok(args)
+    }
+
+    /// Loads the configuration from `Layered`, then uses the arguments in `matches`
+    ///
+    /// So the precedence is the defaults, then the files, then the environment
+    /// variables, then the command-line
+    fn from_layers(matches: &ArgMatches) -> Result<Self, Error> {
+        Layered::<Self>::new().load()?.apply_matches(matches)
+    }
+
+    /// Uses the arguments in `matches` (from `args`) as overrides
+    ///
+    /// Arguments which weren't given are skipped. See `Configurable::apply_overrides`
+    fn apply_matches(self, matches: &ArgMatches) -> Result<Self, Error> {
+        let defaults = toml::Value::try_from(Self::load_embedded()?)?;
+        let mut overrides = vec![];
+        for key in flags(&defaults) {
+            if let Ok(Some(value)) = matches.try_get_one::<String>(&key) {
+                overrides.push(format!("{}={}", key, value));
+            }
+        }
+        if let Ok(Some(sets)) = matches.try_get_many::<String>(SET) {
+            overrides.extend(sets.cloned());
+        }
+        self.apply_overrides(overrides)
+    }
+}
+
+impl<T: Configurable> CliConfigurable for T {}
+
+/// The id and name of the argument for any value
+const SET: &str = "set";
+
+/// The dotted keys which get their own argument
+fn flags(defaults: &toml::Value) -> Vec<String> {
+    value::leaves(defaults)
+        .into_iter()
+        .filter(|key| key != SET && key != "help")
+        .collect()
+}
+
+/// The value at the dotted `key`
+fn get<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
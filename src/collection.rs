@@ -0,0 +1,155 @@
+use super::*;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+/// Many instances of a `Configurable`, each stored in a file of its own
+///
+/// The entries are in a directory named after the stem of `NAME` (e.g. `servers` for
+/// `servers.toml`), in the directory of the `LOCATION`. Each file is named after its
+/// id and the extension of `NAME` (e.g. `servers/home.toml`). An id can't be empty,
+/// or contain a `.` or a path separator, otherwise it is an `Error::InvalidName`
+///
+/// The entries are read and written like the file (e.g. with `MAX_SIZE`, `BACKUPS`
+/// and `CHECKSUM`), but without the environment variables, `SECRETS` or `Overrides`
+///
+/// ```no_run
+/// # use configurable::{Collection, Configurable, Location, TomlFormat};
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct Server {
+///     address: String,
+/// }
+///
+/// impl Configurable for Server {
+///     const ORGANIZATION: &'static str = "museun";
+///     const APPLICATION: &'static str = "foobar";
+///     const NAME: &'static str = "servers.toml";
+///     const LOCATION: Location = Location::Data;
+///     type Format = TomlFormat;
+/// }
+///
+/// let server = Server { address: "localhost:6667".into() };
+/// Collection::<Server>::insert("home", &server).unwrap();
+///
+/// for (id, server) in Collection::<Server>::load_all().unwrap() {
+///     println!("{}: {}", id, server.address);
+/// }
+///
+/// Collection::<Server>::remove("home").unwrap();
+/// ```
+pub struct Collection<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Configurable> Collection<T> {
+    /// Returns the directory of the entries, without creating it
+    pub fn dir() -> Result<PathBuf, Error> {
+        let name = T::name();
+        let stem = name.rsplit_once('.').map_or(&*name, |(stem, _)| stem);
+        T::try_dir().map(|dir| dir.join(stem))
+    }
+
+    /// Returns a `PathBuf` to the file of the entry `id`, without creating the directory
+    pub fn path(id: &str) -> Result<PathBuf, Error> {
+        if !configurable::valid_name(id) {
+            return Err(Error::InvalidName(id.to_string()));
+        }
+        let name = T::name();
+        let file = match name.rsplit_once('.') {
+            Some((_, ext)) => format!("{}.{}", id, ext),
+            None => id.to_string(),
+        };
+        Self::dir().map(|dir| dir.join(file))
+    }
+
+    /// The ids of the entries, sorted
+    pub fn ids() -> Result<Vec<String>, Error> {
+        let dir = Self::dir()?;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(Error::io(Op::Read, &dir)(err)),
+        };
+        let name = T::name();
+        let suffix = name
+            .rsplit_once('.')
+            .map_or_else(String::new, |(_, ext)| format!(".{}", ext));
+        let mut ids = entries
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|file| {
+                let id = file.strip_suffix(&*suffix)?;
+                Some(id.to_string()).filter(|id| configurable::valid_name(id))
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Loads every entry, by its id
+    ///
+    /// Every entry is loaded, and all of the errors are returned together
+    pub fn load_all() -> Result<BTreeMap<String, T>, Error> {
+        let (mut entries, mut errors) = (BTreeMap::new(), vec![]);
+        for id in Self::ids()? {
+            match Self::load(&id) {
+                Ok(entry) => {
+                    entries.insert(id, entry);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+        match Error::multiple(errors) {
+            Some(err) => Err(err),
+            None => Ok(entries),
+        }
+    }
+
+    /// Loads the entry `id`, returning `None` if it doesn't exist
+    pub fn get(id: &str) -> Result<Option<T>, Error> {
+        match Self::load(id) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(Error::NotFound(..)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Saves `entry` as `id`, replacing the entry which is already there
+    ///
+    /// This creates the directory, and takes the lock of the file
+    pub fn insert(id: &str, entry: &T) -> Result<(), Error> {
+        let path = Self::path(id)?;
+        file::writable::<T>(&path)?;
+        // serialize before creating anything, so a failure doesn't leave an empty directory
+        let data = entry.save_bytes()?;
+        T::ensure_dir()?;
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        let _lock = file::lock(&path)?;
+        file::write::<T>(&path, &data)?;
+        entry.after_save(&path);
+        Ok(())
+    }
+
+    /// Removes the entry `id`, if it exists
+    pub fn remove(id: &str) -> Result<(), Error> {
+        let path = Self::path(id)?;
+        if !path.exists() {
+            return Ok(());
+        }
+        file::writable::<T>(&path)?;
+        let _lock = file::lock(&path)?;
+        file::remove::<T>(&path)
+    }
+
+    /// Loads the entry `id`, a missing file is an `Error::NotFound`
+    fn load(id: &str) -> Result<T, Error> {
+        let path = Self::path(id)?;
+        let _lock = file::lock_shared(&path)?;
+        let data = file::read::<T>(&path)?;
+        T::load_bytes(&data).map_err(Error::in_file(&path))
+    }
+}
@@ -0,0 +1,134 @@
+use super::*;
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+/// A configuration file whose identity is given at runtime, rather than by a `Configurable`
+///
+/// This is for libraries which load the files of several applications, or when the
+/// identity isn't known at compile time. The `Format` is detected from the extension
+/// of the name (like `Detect`)
+///
+/// Only the file itself is used, so none of the other features of `Configurable`
+/// (e.g. environment variables, backups or checksums) apply
+///
+/// ```no_run
+/// # use configurable::{ConfigFile, Location};
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct Settings { port: u16 }
+/// let file = ConfigFile::<Settings>::new("museun", "foobar", "config.toml")
+///     .location(Location::Config);
+/// let mut settings = file.load_or_default().unwrap();
+/// settings.port = 8080;
+/// file.save(&settings).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ConfigFile<T> {
+    qualifier: String,
+    organization: String,
+    application: String,
+    name: String,
+    location: Location,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ConfigFile<T> {
+    fn clone(&self) -> Self {
+        Self {
+            qualifier: self.qualifier.clone(),
+            organization: self.organization.clone(),
+            application: self.application.clone(),
+            name: self.name.clone(),
+            location: self.location,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> ConfigFile<T>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    /// Creates the file `name` for the `organization` and `application`
+    ///
+    /// The qualifier defaults to `com.github`, and the location to `Location::Config`
+    pub fn new(
+        organization: impl Into<String>,
+        application: impl Into<String>,
+        name: impl Into<String>,
+    ) -> Self {
+        Self {
+            qualifier: "com.github".to_string(),
+            organization: organization.into(),
+            application: application.into(),
+            name: name.into(),
+            location: Location::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the qualifier (e.g. `com.github`)
+    pub fn qualifier(mut self, qualifier: impl Into<String>) -> Self {
+        self.qualifier = qualifier.into();
+        self
+    }
+
+    /// Sets which directory the file is stored in
+    pub fn location(mut self, location: Location) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// Returns the directory, without creating it
+    pub fn try_dir(&self) -> Result<PathBuf, Error> {
+        let (qualifier, org, app) = (&self.qualifier, &self.organization, &self.application);
+        self.location.dir(qualifier, org, app)
+    }
+
+    /// Ensures the directory exists
+    pub fn ensure_dir(&self) -> Result<PathBuf, Error> {
+        let dir = self.try_dir()?;
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        Ok(dir)
+    }
+
+    /// Returns a `PathBuf` to the file, ensuring the directory exists
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        self.ensure_dir().map(|dir| dir.join(&self.name))
+    }
+
+    /// Returns a `PathBuf` to the file, without creating the directory
+    pub fn try_path(&self) -> Result<PathBuf, Error> {
+        self.try_dir().map(|dir| dir.join(&self.name))
+    }
+
+    /// Tries to load the file
+    ///
+    /// A missing file is an `Error::NotFound`
+    pub fn load(&self) -> Result<T, Error> {
+        let path = self.try_path()?;
+        let _lock = file::lock_shared(&path)?;
+        let data = fs::read(&path).map_err(Error::io(Op::Read, &path))?;
+        configurable::detect_deserialize(&self.name, &data).map_err(Error::in_file(&path))
+    }
+
+    /// Tries to load the file, or uses `Default::default()` if it doesn't exist
+    pub fn load_or_default(&self) -> Result<T, Error> {
+        match self.load() {
+            Err(Error::NotFound(..)) => Ok(T::default()),
+            res => res,
+        }
+    }
+
+    /// Saves the value to the file, creating the directory if needed
+    ///
+    /// This takes an exclusive lock, and the file is replaced atomically
+    pub fn save(&self, value: &T) -> Result<(), Error> {
+        let data = configurable::detect_serialize_with(&self.name, value, &SaveOptions::default())?;
+        let path = self.path()?;
+        let _lock = file::lock(&path)?;
+        file::replace(&path, &data, None)
+    }
+}
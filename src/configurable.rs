@@ -1,61 +1,195 @@
 use super::*;
 
+use std::borrow::Cow;
 use std::fs;
-use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// Where the files of a `Configurable` are stored, see `Configurable::LOCATION`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// The equivalent of `$XDG_CONFIG_HOME`, for files which are edited by the user
+    #[default]
+    Config,
+    /// The equivalent of `$XDG_DATA_HOME`, for files which are written by the application
+    Data,
+    /// The system-wide configuration directory, see `SystemConfig`
+    ///
+    /// Unlike `SystemConfig`, this doesn't stop the files from being written (set
+    /// `Configurable::READ_ONLY` for that)
+    System,
+}
+
+impl Location {
+    /// The directory for the `qualifier`, `organization` and `application`
+    pub(crate) fn dir(
+        self,
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<PathBuf, Error> {
+        if let Location::System = self {
+            return Ok(system_dir(organization, application));
+        }
+        let dirs = directories::ProjectDirs::from(qualifier, organization, application)
+            .ok_or(Error::NoHomeDirectory)?;
+        match self {
+            Location::Data => Ok(dirs.data_dir().to_owned()),
+            _ => Ok(dirs.config_dir().to_owned()),
+        }
+    }
+}
+
+/// The directory of `T` in `location`
+fn location_dir<T: Configurable>(location: Location) -> Result<PathBuf, Error> {
+    let (organization, application) = identity::<T>()?;
+    location.dir(&T::qualifier(), &organization, &application)
+}
+
+/// The organization and application of `T`, either of them being empty is an
+/// `Error::InvalidName`
+pub(crate) fn identity<T: Configurable>() -> Result<(Cow<'static, str>, Cow<'static, str>), Error> {
+    let (organization, application) = (T::organization(), T::application());
+    for name in [&organization, &application] {
+        if name.is_empty() {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+    }
+    Ok((organization, application))
+}
 
 /// A Configurable type that loads from the equivalent of `$XDG_DATA_HOME`
+#[deprecated(note = "set `Configurable::LOCATION` to `Location::Data` instead")]
 pub trait Data: Configurable {
+    /// Returns the directory, without creating it
+    fn try_dir() -> Result<PathBuf, Error> {
+        location_dir::<Self>(Location::Data)
+    }
+
     /// Ensures the directory exists
     fn ensure_dir() -> Result<PathBuf, Error> {
-        let (qualifier, org, app) = (Self::QUALIFIER, Self::ORGANIZATION, Self::APPLICATION);
-        let dirs = directories::ProjectDirs::from(qualifier, org, app)
-            .expect("system must have a valid $HOME directory");
-        let dirs = dirs.data_dir();
-        fs::create_dir_all(&dirs).map_err(Error::Write)?;
-        Ok(dirs.to_owned())
+        #[allow(deprecated)]
+        let dir = <Self as Data>::try_dir()?;
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        Ok(dir)
+    }
+}
+
+/// A Configurable type that loads from the system-wide configuration directory
+///
+/// This is `/etc/{APPLICATION}` on Unix, and `%PROGRAMDATA%\{ORGANIZATION}\{APPLICATION}`
+/// on Windows. These are usually only writable by an administrator, so the files
+/// are read-only unless `WRITABLE` is set
+///
+/// ```
+/// # use configurable::{Configurable, Error, SystemConfig, TomlFormat};
+/// # use std::path::PathBuf;
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct Daemon { port: u16 }
+/// impl SystemConfig for Daemon {}
+///
+/// impl Configurable for Daemon {
+///     const ORGANIZATION: &'static str = "museun";
+///     const APPLICATION: &'static str = "daemon";
+///     const NAME: &'static str = "daemon.toml";
+///     type Format = TomlFormat;
+///
+///     fn try_dir() -> Result<PathBuf, Error> {
+///         <Self as SystemConfig>::try_dir()
+///     }
+///
+///     fn ensure_dir() -> Result<PathBuf, Error> {
+///         <Self as SystemConfig>::ensure_dir()
+///     }
+/// }
+///
+/// # #[cfg(unix)]
+/// assert_eq!(Daemon::try_path().unwrap(), PathBuf::from("/etc/daemon/daemon.toml"));
+/// assert!(Daemon::default().save().is_err());
+/// ```
+pub trait SystemConfig: Configurable {
+    /// Whether the files can be written, defaults to `false`
+    const WRITABLE: bool = false;
+
+    /// Returns the directory, without creating it
+    fn try_dir() -> Result<PathBuf, Error> {
+        location_dir::<Self>(Location::System)
+    }
+
+    /// Ensures the directory exists
+    ///
+    /// This is an error unless `WRITABLE` is set, so the files cannot be saved
+    fn ensure_dir() -> Result<PathBuf, Error> {
+        let dir = <Self as SystemConfig>::try_dir()?;
+        if !Self::WRITABLE {
+            let source = std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "the system-wide configuration is read-only",
+            );
+            return Err(Error::Io {
+                op: Op::Write,
+                path: dir,
+                source,
+            });
+        }
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        Ok(dir)
+    }
+}
+
+/// The system-wide configuration directory for `application`
+pub(crate) fn system_dir(organization: &str, application: &str) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let dir = std::env::var_os("PROGRAMDATA").unwrap_or_else(|| r"C:\ProgramData".into());
+        PathBuf::from(dir).join(organization).join(application)
+    }
+    #[cfg(not(windows))]
+    {
+        let _ = organization;
+        PathBuf::from("/etc").join(application)
     }
 }
 
 /// A Configurable type that loads from the equivalent of `$XDG_CONFIG_HOME`
+#[deprecated(note = "this is the default `Configurable::LOCATION`, so it isn't needed")]
 pub trait Config: Configurable {
+    /// Returns the directory, without creating it
+    fn try_dir() -> Result<PathBuf, Error> {
+        location_dir::<Self>(Location::Config)
+    }
+
     /// Ensures the directory exists
     fn ensure_dir() -> Result<PathBuf, Error> {
-        let (qualifier, org, app) = (Self::QUALIFIER, Self::ORGANIZATION, Self::APPLICATION);
-        let dirs = directories::ProjectDirs::from(qualifier, org, app)
-            .expect("system must have a valid $HOME directory");
-        let dirs = dirs.config_dir();
-        fs::create_dir_all(&dirs).map_err(Error::Write)?;
-        Ok(dirs.to_owned())
+        #[allow(deprecated)]
+        let dir = <Self as Config>::try_dir()?;
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        Ok(dir)
     }
 }
 
 /// Trait to provide easier loaded/saving of a `config` type
 ///
 /// Provide static strs for `QUALIFIER`, `ORGANIZATION`, `APPLICATION` and `NAME`
+/// and the `Format` the file is stored in. The `LOCATION` is where it is stored
 ///
 /// Will which will produce $CONFIG_PATH/qualifier.organization.application/name
 ///
 /// # Configuration-style configs (e.g. stuff that should be human editable)
 /// ```
 /// use serde::{Serialize, Deserialize};
-/// use std::path::PathBuf;
-/// use configurable::{Config, Data, Configurable, Error};
+/// use configurable::{Configurable, TomlFormat};
 ///
 /// // Default is required
 /// #[derive(Default, Serialize, Deserialize)]
 /// struct MyConfig;
 ///
 /// // For configurations (e.g. foo.toml)
-/// impl Config for MyConfig {};
 /// impl Configurable for MyConfig {
 ///     const ORGANIZATION: &'static str = "museun";
 ///     const APPLICATION: &'static str = "foobar";
 ///     const NAME: &'static str = "config.toml";
-///
-///     fn ensure_dir() -> Result<PathBuf, Error> {
-///         // Config `configs`
-///         <Self as Config>::ensure_dir()
-///     }
+///     type Format = TomlFormat;
 /// }
 /// // will place it here:
 /// // -> "~/.config/com.github/museun/foobar/config.toml
@@ -63,29 +197,42 @@ pub trait Config: Configurable {
 ///
 /// # Data-style configurations (e.g. formats outside of toml)
 /// ```
+/// # #[cfg(feature = "json")] {
 /// use serde::{Serialize, Deserialize};
-/// use std::path::PathBuf;
-/// use configurable::{Config, Data, Configurable, Error};
+/// use configurable::{Configurable, JsonFormat, Location};
 ///
 /// // Default is required
 /// #[derive(Default, Serialize, Deserialize)]
 /// struct MyMap { map: std::collections::HashMap<String,i32> }
 ///
-/// // For configurations (e.g. foo.toml)
-/// impl Data for MyMap {};
+/// // For data (e.g. foo.json)
 /// impl Configurable for MyMap {
 ///     const ORGANIZATION: &'static str = "museun";
 ///     const APPLICATION: &'static str = "foobar";
 ///     const NAME: &'static str = "mapping.json";
-///
-///     fn ensure_dir() -> Result<PathBuf, Error> {
-///         // Data `configs`
-///         <Self as Data>::ensure_dir()
-///     }
+///     const LOCATION: Location = Location::Data;
+///     type Format = JsonFormat;
 /// }
 /// // will place it here:
 /// // -> "~/.local/share/com.github/museun/foobar/mapping.json
-/// ````
+/// # }
+/// ```
+///
+/// # Deriving it (with the `derive` feature)
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use configurable::Configurable;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Default, Serialize, Deserialize, Configurable)]
+/// #[configurable(org = "museun", app = "foobar", name = "config.toml", kind = "config")]
+/// struct MyConfig {
+///     name: String,
+/// }
+///
+/// assert!(MyConfig::try_path().unwrap().ends_with("config.toml"));
+/// # }
+/// ```
 pub trait Configurable: Default + serde::Serialize + serde::de::DeserializeOwned {
     /// Qualifier (e.g. "com.github")
     ///
@@ -93,74 +240,1064 @@ pub trait Configurable: Default + serde::Serialize + serde::de::DeserializeOwned
     const QUALIFIER: &'static str = "com.github";
     /// Organization (e.g. "museun" (in github.com/museun))
     ///
-    /// You must provide this
-    const ORGANIZATION: &'static str;
+    /// You must provide this, or override `organization` (otherwise the directory is
+    /// an `Error::InvalidName`)
+    const ORGANIZATION: &'static str = "";
     /// Application (e.g. "foo" (in github.com/museun/foo))
     ///
-    /// You must provide this
-    const APPLICATION: &'static str;
-    /// The name of the toml file, with extension
+    /// You must provide this, or override `application` (otherwise the directory is
+    /// an `Error::InvalidName`)
+    const APPLICATION: &'static str = "";
+    /// The name of the file, with extension
+    ///
+    /// ex: `config.toml`. You must provide this, or override `name` (otherwise the
+    /// path is an `Error::InvalidName`)
+    const NAME: &'static str = "";
+    /// A document, in the `Format`, used for the default instance
+    ///
+    /// When this is `None` then `Default::default()` is used
+    ///
+    /// ex: `Some(include_str!("../default.toml"))`
+    const DEFAULT_SOURCE: Option<&'static str> = None;
+    /// A top-level table, inside of `NAME`, which this type is stored under
     ///
-    /// ex: `config.toml`
-    const NAME: &'static str;
+    /// This allows several types to share a single file. When saving, only
+    /// this table is replaced. The `Format` must be self-describing
+    ///
+    /// ex: `Some("server")` for the `[server]` table of `app.toml`
+    const SECTION: Option<&'static str> = None;
+    /// Dotted paths of fields which are saved in `SECRETS_NAME` instead of `NAME`
+    ///
+    /// `load` stitches them back together. The `Format` must be self-describing
+    ///
+    /// ex: `&["token", "database.password"]`
+    const SECRETS: &'static [&'static str] = &[];
+    /// The name of the file, next to `NAME`, which `SECRETS` are saved in
+    ///
+    /// It is always TOML, whatever the `Format` is. Defaults to `secrets.toml`. The
+    /// secrets of a named instance are saved in a file of their own, named like the
+    /// instance (e.g. `secrets.work.toml`)
+    const SECRETS_NAME: &'static str = "secrets.toml";
+    /// How many old copies of the file to keep when saving
+    ///
+    /// These are rotated as `NAME.1` (the newest) to `NAME.{BACKUPS}` (the oldest)
+    const BACKUPS: usize = 0;
+    /// Whether a checksum is saved next to the file, as `NAME.sha256`
+    ///
+    /// `load` returns `Error::Corrupt` if the file doesn't match it
+    #[cfg(feature = "checksum")]
+    const CHECKSUM: bool = false;
+    /// Whether `load_or_default` falls back to the newest valid backup, when the
+    /// file cannot be parsed
+    ///
+    /// The file is left as is, see `BACKUPS`
+    const RECOVER: bool = false;
+    /// The largest file, in bytes, which will be loaded
+    ///
+    /// Larger files are an `Error::TooLarge`, rather than being read into memory
+    const MAX_SIZE: Option<u64> = None;
+    /// The Unix mode the files are saved with (e.g. `Some(0o600)` for files with secrets)
+    ///
+    /// When this is `None` the mode comes from the umask. Other platforms don't have
+    /// modes, so saving with one is an `Error::Io` there (set it with `#[cfg(unix)]`).
+    /// The files then inherit the permissions of the directory, which on Windows is
+    /// only accessible by the user
+    const FILE_MODE: Option<u32> = None;
+    /// Whether `delete` and `reset` move the files to the trash, rather than removing them
+    ///
+    /// This allows the user to restore them
+    #[cfg(feature = "trash")]
+    const TRASH: bool = false;
+    /// Whether `load` overrides fields with the environment variables named
+    /// `{APPLICATION}_{FIELD}`, e.g. `FOOBAR_PORT=8080`
+    ///
+    /// `APPLICATION` is uppercased, and nested fields are separated by `__` (e.g.
+    /// `FOOBAR_DATABASE__URL`). Values are parsed as the type of the field, and lists
+    /// are comma-separated. This uses the .env file too, see `Env::env`
+    ///
+    /// When the field has no value (e.g. a `None`, or an empty list) its type isn't
+    /// known, so a value which looks like a bool or a number is parsed as one. If the
+    /// configuration cannot be deserialized like that, they are all strings instead
+    ///
+    /// `{APPLICATION}_PROFILE` and `{APPLICATION}_CONFIG_READONLY` are not fields (see
+    /// `active_profile` and `read_only`). These are never saved, like the active `Overrides`
+    const ENV_OVERRIDES: bool = false;
+    /// Whether `load` replaces `${VAR}` in the strings of the file with the
+    /// environment variable, e.g. `path = "${HOME}/music"`
+    ///
+    /// `$$` is a literal `$`, and a missing variable is an `EnvError::Missing`. This
+    /// uses the .env file too, see `Env::env`
+    ///
+    /// Saving an instance which was expanded keeps the variables of the strings which
+    /// weren't changed (e.g. `${HOME}/music`), rather than writing what they expanded to
+    const EXPAND_VARS: bool = false;
+    /// Keys which were renamed, as `(old, new)` dotted paths (e.g.
+    /// `("server.host", "server.address")`)
+    ///
+    /// When a file has an old key, `load` uses its value for the new key (unless
+    /// the file has that too) and calls `deprecated` with a notice for it. Saving
+    /// writes the new key
+    ///
+    /// ```
+    /// # use configurable::{Configurable, Deprecation, TomlFormat};
+    /// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+    /// # struct Foo { address: String }
+    /// impl Configurable for Foo {
+    ///     const ORGANIZATION: &'static str = "some_org";
+    ///     const APPLICATION: &'static str = "foobar";
+    ///     const NAME: &'static str = "config.toml";
+    ///     const DEPRECATED_KEYS: &'static [(&'static str, &'static str)] = &[("host", "address")];
+    ///     type Format = TomlFormat;
+    ///
+    ///     fn deprecated(notice: &Deprecation) {
+    ///         eprintln!("warning: {}", notice);
+    ///     }
+    /// }
+    /// ```
+    const DEPRECATED_KEYS: &'static [(&'static str, &'static str)] = &[];
+    /// Whether `load` merges the fragments in `{NAME}.d/` over the file (e.g.
+    /// `config.toml.d/10-logging.toml`)
+    ///
+    /// These are the files with the same extension as `NAME`, merged in lexical
+    /// order. Tables are merged key by key, any other value replaces the one before
+    /// it. Saving an instance which was merged writes these values to `NAME`. Only
+    /// the user's file has them, not the other layers of `Layered`
+    const FRAGMENTS: bool = false;
+    /// The key in the file which names the files it extends (e.g. `Some("extends")`
+    /// for `extends = "base.toml"`, or `extends = ["base.toml", "colors.toml"]`)
+    ///
+    /// The files are relative to the directory of the file which extends them, and
+    /// they are loaded (and merged, in order) before its keys. They can extend (and
+    /// `INCLUDE`) other files too, but files which extend or include each other are
+    /// an `Error::Cycle`
+    ///
+    /// Saving an instance which was loaded like this writes the merged values,
+    /// without the key
+    const EXTENDS: Option<&'static str> = None;
+    /// The key in the file which names the files it includes, with glob patterns (e.g.
+    /// `Some("include")` for `include = ["extra/*.toml"]`)
+    ///
+    /// The patterns are relative to the directory of the file which includes them, and
+    /// the files they match are merged over its keys, in order (the matches of a
+    /// pattern are sorted). They can include (and `EXTENDS`) other files too, but
+    /// files which include or extend each other are an `Error::Cycle`
+    ///
+    /// Saving an instance which was loaded like this writes the merged values,
+    /// without the key
+    #[cfg(feature = "include")]
+    const INCLUDE: Option<&'static str> = None;
+    /// Whether the files are never written (e.g. for a kiosk, or in tests)
+    ///
+    /// Saving (or removing, or taking a snapshot) is an `Error::ReadOnly`, and a
+    /// default which was created isn't saved. See `read_only`
+    const READ_ONLY: bool = false;
+    /// Where the files are stored, see `try_dir`
+    ///
+    /// Defaults to `Location::Config`
+    const LOCATION: Location = Location::Config;
 
-    /// Ensures the directory exists
+    /// The `Format` used to load and save the file
     ///
-    /// Implement either `Config` or `Data`
-    /// then delegate to it
+    /// ex: `TomlFormat`, or `Detect<Self>` to choose it from the extension of `NAME`
+    type Format: Format;
+
+    /// The qualifier, which is `QUALIFIER` unless this is overridden
+    fn qualifier() -> Cow<'static, str> {
+        Cow::Borrowed(Self::QUALIFIER)
+    }
+
+    /// The organization, which is `ORGANIZATION` unless this is overridden
+    fn organization() -> Cow<'static, str> {
+        Cow::Borrowed(Self::ORGANIZATION)
+    }
+
+    /// The application, which is `APPLICATION` unless this is overridden
+    ///
+    /// ```
+    /// # use configurable::{Configurable, TomlFormat};
+    /// # use std::borrow::Cow;
+    /// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+    /// # struct Foo;
+    /// impl Configurable for Foo {
+    ///     const ORGANIZATION: &'static str = "some_org";
+    ///     type Format = TomlFormat;
+    ///
+    ///     fn application() -> Cow<'static, str> {
+    ///         Cow::Borrowed(env!("CARGO_PKG_NAME"))
+    ///     }
+    ///
+    ///     fn name() -> Cow<'static, str> {
+    ///         std::env::var("MY_CONFIG_NAME").map_or("config.toml".into(), Cow::Owned)
+    ///     }
+    /// }
+    ///
+    /// assert!(Foo::try_path().unwrap().ends_with("configurable/config.toml"));
+    /// ```
+    fn application() -> Cow<'static, str> {
+        Cow::Borrowed(Self::APPLICATION)
+    }
+
+    /// The name of the file, which is `NAME` unless this is overridden
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed(Self::NAME)
+    }
+
+    /// Returns the directory the file is stored in, without creating it
+    ///
+    /// This is the directory of the `LOCATION`
     ///
     /// ```
     /// # use serde::{Serialize, Deserialize};
-    /// # use std::path::PathBuf;
-    /// # use configurable::{Config, Configurable, Error};
+    /// # use configurable::{Configurable, Location, TomlFormat};
     /// # #[derive(Default, Serialize, Deserialize)]
     /// # struct Foo;
-    /// // Config or Data
-    /// impl Config for Foo {};
     /// impl Configurable for Foo {
     ///     const ORGANIZATION: &'static str = "some_org";
     ///     const APPLICATION: &'static str = "foobar";
-    ///     const NAME: &'static str = "config.toml";
-    ///
-    ///     fn ensure_dir() -> Result<PathBuf, Error> {
-    ///         // Config or Data
-    ///         <Self as Config>::ensure_dir()
-    ///     }
+    ///     const NAME: &'static str = "state.toml";
+    ///     const LOCATION: Location = Location::Data;
+    ///     type Format = TomlFormat;
     /// }
     /// ```
-    fn ensure_dir() -> Result<PathBuf, Error>;
+    fn try_dir() -> Result<PathBuf, Error> {
+        location_dir::<Self>(Self::LOCATION)
+    }
+
+    /// Ensures the directory exists
+    ///
+    /// Only the methods which write files create the directory
+    fn ensure_dir() -> Result<PathBuf, Error> {
+        let dir = Self::try_dir()?;
+        fs::create_dir_all(&dir).map_err(Error::io(Op::CreateDir, &dir))?;
+        Ok(dir)
+    }
 
     /// Loads, or defaults the configuration
     ///
     /// Returns a `LoadState`
-    /// * Default meant it created a default instance
-    /// * Loaded meant it created the instance from the file    
+    /// * Default meant it created a default instance (see `load_embedded`)
+    /// * Loaded meant it created the instance from the file
+    /// * Invalid meant the file could not be parsed, so it created a default instance
+    /// * Recovered meant the file could not be parsed, so it was loaded from a backup (see `RECOVER`)
+    ///
+    /// Only a missing file (`Error::NotFound`) is defaulted, a file that cannot be
+    /// read is an error (as is an environment variable which cannot be used, see
+    /// `ENV_OVERRIDES` and `EXPAND_VARS`)
     fn load_or_default() -> Result<LoadState<Self>, Error> {
-        match Self::load() {
-            Ok(this) => Ok(LoadState::Loaded(this)),
-            Err(Error::Read(..)) => Ok(LoadState::Default(Self::default())),
-            Err(err) => Err(err),
+        load_state(Self::load_embedded, false)
+    }
+
+    /// Loads, or defaults and saves the configuration
+    ///
+    /// This is `load_or_default`, but a default instance is also written to `path`,
+    /// so there is a file for the user to edit. An `Invalid` file is left as is
+    ///
+    /// Returns the `LoadState` and the path of the file
+    fn load_or_create_default() -> Result<(LoadState<Self>, PathBuf), Error> {
+        let state = Self::load_or_init(Self::load_embedded)?;
+        Ok((state, Self::path()?))
+    }
+
+    /// Loads, or initializes and saves the configuration
+    ///
+    /// This is `load_or_create_default`, but the initial instance is created by `init`
+    /// rather than `load_embedded`. An `Invalid` file is left as is
+    fn load_or_init<F>(init: F) -> Result<LoadState<Self>, Error>
+    where
+        F: FnOnce() -> Result<Self, Error>,
+    {
+        load_state(init, true)
+    }
+
+    /// Creates the default instance
+    ///
+    /// This parses `DEFAULT_SOURCE`, or uses `Default::default()` if there isn't one
+    ///
+    /// ```
+    /// # use serde::{Serialize, Deserialize};
+    /// # use configurable::{Configurable, TomlFormat};
+    /// #[derive(Default, Serialize, Deserialize)]
+    /// struct Foo { name: String }
+    /// impl Configurable for Foo {
+    ///     const ORGANIZATION: &'static str = "some_org";
+    ///     const APPLICATION: &'static str = "foobar";
+    ///     const NAME: &'static str = "config.toml";
+    ///     // this would typically be `include_str!("default.toml")`
+    ///     const DEFAULT_SOURCE: Option<&'static str> = Some("name = 'bar'");
+    ///     type Format = TomlFormat;
+    /// }
+    ///
+    /// assert_eq!(Foo::load_embedded().unwrap().name, "bar");
+    /// ```
+    fn load_embedded() -> Result<Self, Error> {
+        match Self::DEFAULT_SOURCE {
+            Some(source) => Self::load_bytes(source.as_bytes()),
+            None => Ok(Self::default()),
         }
     }
 
     /// Tries to load the configuration
+    ///
+    /// This takes a shared `lock` (if the file has been saved before)
+    ///
+    /// A missing file is an `Error::NotFound`, and a file that cannot be parsed is an
+    /// `Error::Parse` (with the read error of the `Format`, e.g. `Error::TomlRead`)
     fn load() -> Result<Self, Error> {
-        let dir = Self::ensure_dir()?.join(Self::NAME);
-        let data = fs::read_to_string(dir).map_err(Error::Read)?;
-        toml::from_str(&data).map_err(Error::TomlRead)
+        load_file(&Self::try_path()?, &secrets_path::<Self>(None)?)
+    }
+
+    /// Tries to load the configuration, returning `None` if the file doesn't exist
+    fn load_if_exists() -> Result<Option<Self>, Error> {
+        match Self::load() {
+            Ok(this) => Ok(Some(this)),
+            Err(Error::NotFound(..)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Tries to load the configuration for a profile (e.g. `dev`), where the file
+    /// of the profile (e.g. `config.dev.toml`) is merged over the file
+    ///
+    /// Either of the files can be missing, but not both. See `profile_path`
+    fn load_profile(profile: &str) -> Result<Self, Error> {
+        Env::once(|| {
+            let path = Self::try_path()?;
+            let layers = vec![
+                read_layer::<Self>(&path, true)?,
+                read_layer::<Self>(&Self::profile_path(profile)?, true)?,
+            ];
+            let value = layers
+                .into_iter()
+                .flatten()
+                .reduce(|mut value, layer| {
+                    value::merge(&mut value, layer);
+                    value
+                })
+                .ok_or(Error::NotFound(path))?;
+            apply_env(value.try_into()?).and_then(apply_overrides)
+        })
+    }
+
+    /// Tries to load the configuration for the active profile, or just the file if
+    /// there isn't one
+    ///
+    /// The profile is the environment variable `{APPLICATION}_PROFILE` (e.g.
+    /// `FOOBAR_PROFILE=dev`), see `active_profile`
+    fn load_active_profile() -> Result<Self, Error> {
+        Env::once(|| match Self::active_profile() {
+            Some(profile) => Self::load_profile(&profile),
+            None => Self::load(),
+        })
+    }
+
+    /// The active profile, from the environment variable `{APPLICATION}_PROFILE`
+    ///
+    /// `APPLICATION` is uppercased, like with `ENV_OVERRIDES`. This uses the .env
+    /// file too, see `Env::env`. During a load (or a save) the environment and the
+    /// .env file are only read once, rather than each time this is used
+    fn active_profile() -> Option<String> {
+        let key = format!("{}{}", env::prefix(&Self::application()), env::PROFILE);
+        Env::vars()
+            .remove(&key)
+            .filter(|profile| !profile.is_empty())
+    }
+
+    /// Whether the configuration is read-only, see `READ_ONLY`
+    ///
+    /// This is also set by the environment variable `{APPLICATION}_CONFIG_READONLY`
+    /// (as `1` or `true`), which is read like `active_profile`
+    fn read_only() -> bool {
+        let key = format!("{}{}", env::prefix(&Self::application()), env::READ_ONLY);
+        Self::READ_ONLY
+            || Env::vars()
+                .remove(&key)
+                .is_some_and(|var| var == "1" || var.eq_ignore_ascii_case("true"))
+    }
+
+    /// Finds the project-local file, in the current directory or its parents
+    ///
+    /// This is `.{APPLICATION}.{ext}` or `{APPLICATION}.{ext}`, where `ext` is the
+    /// extension of `NAME` (e.g. `.foobar.toml`), and the nearest one is used. Like
+    /// `Env::find` this stops at the root of a repository. See `Layered::local`
+    fn find_local() -> Option<PathBuf> {
+        let (file, application) = (Self::name(), Self::application());
+        let name = match Path::new(&*file).extension() {
+            Some(ext) => format!("{}.{}", application, ext.to_string_lossy()),
+            None => application.into_owned(),
+        };
+        let dir = std::env::current_dir().ok()?;
+        for dir in dir.ancestors() {
+            for name in [format!(".{}", name), name.clone()].iter() {
+                let path = dir.join(name);
+                if path.is_file() {
+                    return Some(path);
+                }
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Returns a `PathBuf` to the file of a profile, without creating the directory
+    ///
+    /// The profile is before the extension of `NAME` (e.g. `config.dev.toml`)
+    fn profile_path(profile: &str) -> Result<PathBuf, Error> {
+        let name = instance_file(&Self::name(), profile);
+        Self::try_dir().map(|dir| dir.join(name))
+    }
+
+    /// Tries to load a named instance of the configuration (e.g. for an account)
+    ///
+    /// Each instance is a file of its own, named like a profile (e.g. `config.work.toml`
+    /// for `work`), which isn't merged with the file. See `named_path`
+    fn load_named(name: &str) -> Result<Self, Error> {
+        load_file(&Self::named_path(name)?, &secrets_path::<Self>(Some(name))?)
+    }
+
+    /// Tries to save the configuration as a named instance, see `load_named`
+    ///
+    /// The `SECRETS` are saved to a file for the instance, named like it (e.g.
+    /// `secrets.work.toml` for `work`)
+    fn save_named(&self, name: &str) -> Result<(), Error> {
+        let secrets = secrets_path::<Self>(Some(name))?;
+        save_file(self, &Self::named_path(name)?, &secrets)
+    }
+
+    /// The names of the instances which have been saved, sorted
+    ///
+    /// The files of profiles are named the same way, so they are listed too
+    ///
+    /// ```no_run
+    /// # use configurable::Configurable;
+    /// # fn list<T: Configurable>() {
+    /// for name in T::list_named().unwrap() {
+    ///     let account = T::load_named(&name).unwrap();
+    /// #   let _ = account;
+    /// }
+    /// # }
+    /// ```
+    fn list_named() -> Result<Vec<String>, Error> {
+        let dir = Self::try_dir()?;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(Error::io(Op::Read, &dir)(err)),
+        };
+        let name = Self::name();
+        let mut names = entries
+            .flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|file| {
+                // the secrets could be named like instances (e.g. `config.secrets.toml`)
+                Self::SECRETS.is_empty()
+                    || (file != Self::SECRETS_NAME
+                        && instance_of(file, Self::SECRETS_NAME).is_none())
+            })
+            .filter_map(|file| {
+                let instance = instance_of(&file, &name)?;
+                // without an extension, the lock and the backups would look like instances
+                let sidecar = !name.contains('.')
+                    && (instance == "lock"
+                        || instance == "sha256"
+                        || instance.bytes().all(|b| b.is_ascii_digit()));
+                Some(instance.to_string()).filter(|_| !sidecar)
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns a `PathBuf` to the file of a named instance, without creating the directory
+    ///
+    /// The name is before the extension of `NAME` (e.g. `config.work.toml`). It can't
+    /// be empty, or contain a `.` or a path separator, otherwise this is an `Error::InvalidName`
+    fn named_path(name: &str) -> Result<PathBuf, Error> {
+        if !valid_name(name) {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+        let name = instance_file(&Self::name(), name);
+        Self::try_dir().map(|dir| dir.join(name))
+    }
+
+    /// Tries to load the configuration, along with the keys in the file which it doesn't use
+    ///
+    /// The keys are dotted paths (e.g. `server.retrys`), so typos can be reported
+    /// instead of being silently ignored. The file is decoded into a TOML value
+    /// first, like with `SECTION`
+    ///
+    /// ```no_run
+    /// # use configurable::Configurable;
+    /// # fn check<T: Configurable>() {
+    /// let (config, unknown) = T::load_with_unknown_keys().unwrap();
+    /// for key in unknown {
+    ///     eprintln!("warning: unknown key `{}`", key);
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "unknown-keys")]
+    fn load_with_unknown_keys() -> Result<(Self, Vec<String>), Error> {
+        let path = Self::try_path()?;
+        let mut unknown = vec![];
+        let this = {
+            let _lock = file::lock_shared(&path)?;
+            let data = file::read::<Self>(&path)?;
+            let value = read_value::<Self>(&path, &data, Some(&secrets_path::<Self>(None)?))?;
+            serde_ignored::deserialize(value, |key| unknown.push(dotted(&key)))
+                .map_err(Error::TomlRead)
+                .map_err(Error::in_file(&path))?
+        };
+        Ok((apply_env(this).and_then(apply_overrides)?, unknown))
+    }
+
+    /// Tries to load the configuration, along with a `LoadToken` for `load_if_modified`
+    fn load_with_token() -> Result<(Self, LoadToken), Error> {
+        let (this, token) = LoadToken::record(Self::load);
+        this.map(|this| (this, token))
+    }
+
+    /// Tries to load the configuration, if the files changed since the `since` token
+    ///
+    /// Only the modified time and length of the files are checked, so this is
+    /// cheap when nothing changed. Returns `None` if nothing changed
+    ///
+    /// ```no_run
+    /// # use configurable::{Configurable, LoadToken};
+    /// # fn check<T: Configurable>(config: &mut T, token: &mut LoadToken) {
+    /// if let Some((new, next)) = T::load_if_modified(token).unwrap() {
+    ///     *config = new;
+    ///     *token = next;
+    /// }
+    /// # }
+    /// ```
+    fn load_if_modified(since: &LoadToken) -> Result<Option<(Self, LoadToken)>, Error> {
+        if since.is_current() {
+            return Ok(None);
+        }
+        Self::load_with_token().map(Some)
+    }
+
+    /// Tries to load the configuration from the reader
+    ///
+    /// This is streamed if the `Format` supports it
+    ///
+    /// ```
+    /// # use serde::{Serialize, Deserialize};
+    /// # use configurable::{Configurable, TomlFormat};
+    /// # #[derive(Default, Serialize, Deserialize)]
+    /// # struct Foo { name: String }
+    /// # impl Configurable for Foo {
+    /// #     const ORGANIZATION: &'static str = "some_org";
+    /// #     const APPLICATION: &'static str = "foobar";
+    /// #     const NAME: &'static str = "config.toml";
+    /// #     type Format = TomlFormat;
+    /// # }
+    /// let foo = Foo::load_from(&b"name = 'bar'"[..]).unwrap();
+    /// assert_eq!(foo.name, "bar");
+    ///
+    /// let mut out = vec![];
+    /// foo.save_to(&mut out).unwrap();
+    /// assert_eq!(out, b"name = 'bar'\n");
+    /// ```
+    fn load_from(mut reader: impl std::io::Read) -> Result<Self, Error> {
+        if Self::SECTION.is_none() {
+            return Self::Format::deserialize_reader(reader);
+        }
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Self::load_bytes(&data)
+    }
+
+    /// Tries to load the configuration from bytes encoded in its `Format`
+    fn load_bytes(data: &[u8]) -> Result<Self, Error> {
+        if Self::SECTION.is_none() {
+            return Self::Format::deserialize(data);
+        }
+        decode_document::<Self, Self::Format>(data)?
+            .ok_or_else(missing_section::<Self>)?
+            .try_into()
+            .map_err(Error::TomlRead)
+    }
+
+    /// Applies overrides (e.g. `server.port=8080`, from a `--set` flag) over the
+    /// configuration
+    ///
+    /// Each override is a dotted key, then `=`, then the value. Values are parsed
+    /// as the type of the field, like with `ENV_OVERRIDES`. With the `unknown-keys`
+    /// feature, a key which isn't a field is an `Error::Override`
+    ///
+    /// ```
+    /// # use configurable::{Configurable, TomlFormat};
+    /// #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    /// struct Foo {
+    ///     port: u16,
+    ///     hosts: Vec<String>,
+    /// }
+    /// # impl Configurable for Foo {
+    /// #     const ORGANIZATION: &'static str = "some_org";
+    /// #     const APPLICATION: &'static str = "foobar";
+    /// #     const NAME: &'static str = "config.toml";
+    /// #     type Format = TomlFormat;
+    /// # }
+    ///
+    /// let foo = Foo::default().apply_overrides(&["port=8080", "hosts=a,b"]).unwrap();
+    /// assert_eq!(foo.port, 8080);
+    /// assert_eq!(foo.hosts, vec!["a", "b"]);
+    ///
+    /// # #[cfg(feature = "unknown-keys")] {
+    /// let err = Foo::default().apply_overrides(&["prot=8080"]).unwrap_err();
+    /// assert_eq!(err.to_string(), "cannot apply `prot=8080`: `prot` is not a known key");
+    /// # }
+    /// ```
+    fn apply_overrides<I>(self, overrides: I) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let overrides = overrides
+            .into_iter()
+            .map(|assignment| assignment.as_ref().to_string())
+            .collect::<Vec<_>>();
+        let set = |value: &mut toml::Value, guess| {
+            let mut applied = vec![];
+            for assignment in &overrides {
+                let error = |error: String| Error::Override {
+                    assignment: assignment.clone(),
+                    error,
+                };
+                let (key, var) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| error("expected `key=value`".to_string()))?;
+                let path = key
+                    .trim()
+                    .split('.')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                if path.iter().any(String::is_empty) {
+                    return Err(error(format!("`{}` is not a valid key", key.trim())));
+                }
+                let path = value::set_parsed(value, &path, var.trim(), guess).map_err(error)?;
+                applied.push((path, assignment.clone()));
+            }
+            Ok(applied)
+        };
+        let value = toml::Value::try_from(&self)?;
+        deserialize_parsed(value, set, |value, applied| {
+            deserialize_known(value, applied)
+        })
+        .map(|(this, _)| this)
+    }
+
+    /// Merges the keys in `patch` (a snippet of toml, or json with the `json` feature)
+    /// into the configuration
+    ///
+    /// Only the keys in the patch are changed, and tables are merged key by key. A json
+    /// object is a merge patch (RFC 7386), where a `null` resets the value to its
+    /// default. With the `unknown-keys` feature, a key which isn't a field is an
+    /// `Error::Override`. If the patch cannot be used then the configuration isn't changed
+    ///
+    /// ```
+    /// # use configurable::{Configurable, TomlFormat};
+    /// #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    /// struct Foo {
+    ///     name: String,
+    ///     server: Server,
+    /// }
+    ///
+    /// #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    /// struct Server {
+    ///     port: u16,
+    ///     host: String,
+    /// }
+    /// # impl Configurable for Foo {
+    /// #     const ORGANIZATION: &'static str = "some_org";
+    /// #     const APPLICATION: &'static str = "foobar";
+    /// #     const NAME: &'static str = "config.toml";
+    /// #     type Format = TomlFormat;
+    /// # }
+    ///
+    /// let mut foo = Foo::default();
+    /// foo.apply_patch("name = 'foo'\n[server]\nport = 8080\nhost = 'localhost'").unwrap();
+    /// assert_eq!((foo.server.port, foo.server.host.as_str()), (8080, "localhost"));
+    ///
+    /// # #[cfg(feature = "json")] {
+    /// foo.apply_patch(r#"{ "server": { "host": null } }"#).unwrap();
+    /// assert_eq!((foo.server.port, foo.server.host.as_str()), (8080, ""));
+    /// # }
+    ///
+    /// # #[cfg(feature = "unknown-keys")]
+    /// assert!(foo.apply_patch("name = 'bar'\nprot = 1").is_err());
+    /// assert_eq!(foo.name, "foo");
+    /// ```
+    fn apply_patch(&mut self, patch: &str) -> Result<(), Error> {
+        let mut value = toml::Value::try_from(&*self)?;
+        let keys = match patch.trim_start().starts_with('{') {
+            #[cfg(feature = "json")]
+            true => {
+                let patch = serde_json::from_str(patch).map_err(Error::JsonRead)?;
+                let defaults = toml::Value::try_from(Self::default())?;
+                value::merge_patch(&mut value, patch, Some(&defaults))?
+            }
+            _ => {
+                let patch = toml::from_str::<toml::Value>(patch)?;
+                let keys = value::leaves(&patch);
+                value::merge(&mut value, patch);
+                keys
+            }
+        };
+        let applied: Vec<_> = keys
+            .into_iter()
+            .map(|key| {
+                let assignment = match key.split('.').try_fold(&value, |v, p| v.get(p)) {
+                    Some(item) => format!("{}={}", key, item),
+                    None => format!("{}=null", key),
+                };
+                (key, assignment)
+            })
+            .collect();
+        *self = deserialize_known(value, &applied)?;
+        Ok(())
+    }
+
+    /// The values which are different in `other`, with their dotted keys
+    ///
+    /// Both are serialized, then tables are compared key by key (and any other values as
+    /// a whole). This can be used to log what changed when a file is reloaded
+    ///
+    /// ```
+    /// # use configurable::{Configurable, TomlFormat};
+    /// #[derive(Default, serde::Serialize, serde::Deserialize)]
+    /// struct Foo {
+    ///     name: String,
+    ///     port: Option<u16>,
+    /// }
+    /// # impl Configurable for Foo {
+    /// #     const ORGANIZATION: &'static str = "some_org";
+    /// #     const APPLICATION: &'static str = "foobar";
+    /// #     const NAME: &'static str = "config.toml";
+    /// #     type Format = TomlFormat;
+    /// # }
+    ///
+    /// let old = Foo { name: "foo".into(), port: None };
+    /// let new = Foo { name: "bar".into(), port: Some(80) };
+    /// let changes = old.diff(&new).unwrap();
+    /// assert_eq!(changes[0].to_string(), r#"name: "foo" -> "bar""#);
+    /// assert_eq!(changes[1].to_string(), "port: 80 was added");
+    /// ```
+    fn diff(&self, other: &Self) -> Result<Vec<ChangedKey>, Error> {
+        let (old, new) = (toml::Value::try_from(self)?, toml::Value::try_from(other)?);
+        Ok(diff::diff(&old, &new))
     }
 
     /// Tries to save the configuration
+    ///
+    /// Any `SECRETS` are saved to `SECRETS_NAME`. Files whose contents wouldn't
+    /// change are not written. This takes the `lock`
     fn save(&self) -> Result<(), Error> {
-        let dir = Self::ensure_dir()?.join(Self::NAME);
-        let s = toml::to_string_pretty(&self).map_err(Error::TomlWrite)?;
-        fs::write(dir, s).map_err(Error::Write)
+        save_file(self, &Self::try_path()?, &secrets_path::<Self>(None)?)
+    }
+
+    /// Calls `before_save`, then saves the configuration
+    fn save_mut(&mut self) -> Result<(), Error> {
+        self.before_save();
+        self.save()
+    }
+
+    /// Called before the configuration is saved by `save_mut`, `update` or `ConfigHandle`
+    ///
+    /// This can normalize the data (e.g. sorting lists). `save` only has a `&self`, so
+    /// it doesn't call this
+    fn before_save(&mut self) {}
+
+    /// Called when a file uses one of the `DEPRECATED_KEYS`, while it is loaded
+    fn deprecated(notice: &Deprecation) {
+        let _ = notice;
+    }
+
+    /// Called after the configuration is saved to `path`
+    fn after_save(&self, path: &Path) {
+        let _ = path;
+    }
+
+    /// Loads the configuration, applies `f` to it, then saves it
+    ///
+    /// The file is locked for all of this, so other processes updating
+    /// it cannot interleave with it. A missing file starts from `load_embedded`
+    ///
+    /// `f` is given the configuration without the environment overrides (see
+    /// `ENV_OVERRIDES`) or the active `Overrides`, so they aren't saved. Returns the
+    /// updated instance, with them
+    fn update<F>(f: F) -> Result<Self, Error>
+    where
+        F: FnOnce(&mut Self),
+    {
+        Env::once(|| {
+            let _lock = Self::lock()?;
+            let (path, secrets) = (Self::try_path()?, secrets_path::<Self>(None)?);
+            let read = read_file::<Self>(&path, &secrets);
+            let mut this = match read.and_then(|this| layer_env(this, Self::EXPAND_VARS, false)) {
+                Ok(this) => this,
+                Err(Error::NotFound(..)) => Self::load_embedded()?,
+                Err(err) => return Err(err),
+            };
+            f(&mut this);
+            this.save_mut()?;
+            layer_env(this, false, Self::ENV_OVERRIDES).and_then(apply_overrides)
+        })
+    }
+
+    /// Locks the file, until the returned `FileLock` is dropped
+    ///
+    /// The lock is advisory, and held across processes. `load` and `save` also lock
+    /// the file, this is for longer sections (e.g. a load, then a save). It is an
+    /// `Error::ReadOnly` if the files are read-only
+    ///
+    /// ```no_run
+    /// # use configurable::Configurable;
+    /// # fn bump<T: Configurable>(f: impl Fn(&mut T)) {
+    /// let lock = T::lock().unwrap();
+    /// let mut this = T::load().unwrap();
+    /// f(&mut this);
+    /// this.save().unwrap();
+    /// drop(lock);
+    /// # }
+    /// ```
+    fn lock() -> Result<FileLock, Error> {
+        let path = Self::try_path()?;
+        // checked first, so the directory and the lock aren't created either
+        file::writable::<Self>(&path)?;
+        Self::ensure_dir()?;
+        file::lock(&path)
+    }
+
+    /// The process-wide instance, which is loaded on first use (see `Global`)
+    fn global() -> &'static Global<Self>
+    where
+        Self: Send + Sync + 'static,
+    {
+        global::global()
+    }
+
+    /// Watches the file for changes, checking it every `interval` (see `Watcher`)
+    fn watch(interval: std::time::Duration) -> Result<Watcher<Self>, Error>
+    where
+        Self: Send + 'static,
+    {
+        Watcher::new(interval)
+    }
+
+    /// Returns whether the configuration has been saved
+    ///
+    /// If there is a `SECTION` then the file must also contain it
+    fn exists() -> Result<bool, Error> {
+        let path = Self::try_path()?;
+        if Self::SECTION.is_none() {
+            return path.try_exists().map_err(Error::io(Op::Read, &path));
+        }
+        match fs::read(&path) {
+            Ok(data) => decode_document::<Self, Self::Format>(&data)
+                .map(|doc| doc.is_some())
+                .map_err(Error::in_file(&path)),
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(Error::io(Op::Read, &path)(err)),
+        }
+    }
+
+    /// Deletes the saved configuration, along with its `SECRETS_NAME`
+    ///
+    /// If there is a `SECTION` then only it is removed from the file. Nothing
+    /// happens if the configuration wasn't saved
+    fn delete() -> Result<(), Error> {
+        let (dir, path) = (Self::try_dir()?, Self::try_path()?);
+        if !dir.exists() {
+            return Ok(());
+        }
+        file::writable::<Self>(&path)?;
+        let _lock = file::lock(&path)?;
+        if !Self::SECRETS.is_empty() {
+            remove_document::<Self, TomlFormat>(&dir.join(Self::SECRETS_NAME))?;
+        }
+        remove_document::<Self, Self::Format>(&path)
+    }
+
+    /// Deletes the saved configuration, then saves and returns the default instance
+    ///
+    /// The default instance is from `load_embedded`
+    fn reset() -> Result<Self, Error> {
+        let _lock = Self::lock()?;
+        Self::delete()?;
+        let this = Self::load_embedded()?;
+        this.save()?;
+        Ok(this)
+    }
+
+    /// Returns the paths of the existing `BACKUPS`
+    ///
+    /// These are ordered from newest to oldest
+    fn backups() -> Result<Vec<PathBuf>, Error> {
+        let path = Self::try_path()?;
+        let backups = (1..=Self::BACKUPS)
+            .map(|n| file::backup_path(&path, n))
+            .filter(|path| path.exists())
+            .collect();
+        Ok(backups)
+    }
+
+    /// Copies the saved file into the `history` directory, next to it
+    ///
+    /// `SECRETS_NAME` is not copied. Returns the new `Snapshot`
+    fn snapshot() -> Result<Snapshot, Error> {
+        let (dir, path) = (Self::try_dir()?, Self::try_path()?);
+        file::writable::<Self>(&path)?;
+        snapshot::create(&dir, &Self::name())
+    }
+
+    /// Returns the `Snapshot`s of the file, from oldest to newest
+    fn list_snapshots() -> Result<Vec<Snapshot>, Error> {
+        snapshot::list(&Self::try_dir()?, &Self::name())
+    }
+
+    /// Loads the `Snapshot` with the `id`, then saves it as the configuration
+    ///
+    /// Returns the restored instance. An unknown `id` is an `Error::NotFound`
+    fn restore(id: &str) -> Result<Self, Error> {
+        Env::once(|| {
+            let _lock = Self::lock()?;
+            let snapshot = snapshot::find(&Self::try_dir()?, &Self::name(), id)?;
+            let (path, secrets) = (Self::try_path()?, secrets_path::<Self>(None)?);
+            // written as it was, so the variables aren't expanded (and the overrides aren't saved)
+            let this: Self = read_file(snapshot.path(), &secrets)?;
+            write_file(&this, &path, &secrets, Ok)?;
+            this.after_save(&path);
+            apply_env(this).and_then(apply_overrides)
+        })
+    }
+
+    /// Tries to save the configuration to the writer
+    fn save_to(&self, mut out: impl std::io::Write) -> Result<(), Error> {
+        let data = self.save_bytes()?;
+        out.write_all(&data).map_err(Error::Write)
+    }
+
+    /// Tries to save the configuration, keeping the comments and formatting of
+    /// the existing file
+    ///
+    /// Only the values which changed are written. The file must be TOML
+    #[cfg(feature = "preserve")]
+    fn save_preserving(&self) -> Result<(), Error> {
+        file::writable::<Self>(&Self::try_path()?)?;
+        let dir = Self::ensure_dir()?.join(&*Self::name());
+        let _lock = file::lock(&dir)?;
+        let updated = toml::to_string_pretty(self)?;
+        let data = match fs::read_to_string(&dir) {
+            Ok(existing) => preserve::patch(&existing, &updated)?,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => updated,
+            Err(err) => return Err(Error::io(Op::Read, &dir)(err)),
+        };
+        file::write::<Self>(&dir, data.as_bytes())?;
+        self.after_save(&dir);
+        Ok(())
+    }
+
+    /// Tries to write the configuration to `path`, in the `Format` `F`
+    ///
+    /// The file is replaced atomically, with `FILE_MODE`
+    fn convert_to<F: Format>(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        file::writable::<Self>(path)?;
+        let data = F::serialize_with(self, &Self::save_options())?;
+        file::replace(path, &data, Self::FILE_MODE)
+    }
+
+    /// Tries to write the configuration to `path`, instead of the usual location
+    ///
+    /// This writes the same document as `save_bytes`, but without the `SECRETS`, and
+    /// can be read back with `load_from_path` (e.g. for exporting a configuration).
+    /// The file is replaced atomically, with `FILE_MODE`
+    fn save_as(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        save_to_path(self, path.as_ref(), false)
+    }
+
+    /// Tries to write the configuration to `path`, along with its `SECRETS`
+    ///
+    /// This is the same as `save_as`, otherwise
+    fn save_as_with_secrets(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        save_to_path(self, path.as_ref(), true)
+    }
+
+    /// Tries to load the configuration from `path`, instead of the usual location
+    ///
+    /// This is the same as `load_from`, with the file at `path`
+    fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::load_from(file::open::<Self>(path)?).map_err(Error::in_file(path))
+    }
+
+    /// Tries to migrate the configuration from an older file stored in the `Format` `F`
+    ///
+    /// If `old_name` exists in the directory then it is read with `F`, saved as
+    /// `NAME` with this type's `Format` and then archived as `old_name.bak`
+    ///
+    /// Returns `None` if there was nothing to migrate
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "json")] {
+    /// # use configurable::{Configurable, JsonFormat};
+    /// # fn migrate<T: Configurable>() {
+    /// // upgrade users from `config.json` to (e.g.) `config.toml`
+    /// if let Some(config) = T::migrate_format::<JsonFormat>("config.json").unwrap() {
+    ///     eprintln!("migrated config to: {}", T::path().unwrap().display());
+    /// }
+    /// # }
+    /// # }
+    /// ```
+    fn migrate_format<F: Format>(old_name: &str) -> Result<Option<Self>, Error> {
+        let dir = Self::try_dir()?;
+        let old = dir.join(old_name);
+        let data = match fs::read(&old) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::io(Op::Read, &old)(err)),
+        };
+        let this: Self = F::deserialize(&data).map_err(Error::in_file(&old))?;
+
+        // archive it first, `old_name` could be the same as `NAME`
+        let archive = dir.join(format!("{}.bak", old_name));
+        fs::rename(&old, &archive).map_err(Error::io(Op::Rename, &old))?;
+        if let Err(err) = this.save() {
+            let _ = fs::rename(archive, old);
+            return Err(err);
+        }
+        Ok(Some(this))
+    }
+
+    /// The `SaveOptions` used when writing the configuration
+    ///
+    /// Defaults to `SaveOptions::pretty()`
+    fn save_options() -> SaveOptions {
+        SaveOptions::default()
+    }
+
+    /// Tries to encode the configuration into bytes with its `Format`
+    ///
+    /// If there is a `SECTION` then this is a document with only that section
+    fn save_bytes(&self) -> Result<Vec<u8>, Error> {
+        let section = match Self::SECTION {
+            Some(section) => section,
+            None => return Self::Format::serialize_with(self, &Self::save_options()),
+        };
+        let mut doc = toml::value::Table::new();
+        let value = toml::Value::try_from(self)?;
+        doc.insert(section.to_string(), value);
+        Self::Format::serialize_with(&doc, &Self::save_options())
     }
 
     /// Tries to dump the config to the writer
-    fn dump(&self, mut out: impl std::io::Write) -> Result<(), Error> {
-        let s = toml::to_string_pretty(&self).map_err(Error::TomlWrite)?;
-        out.write_all(s.as_bytes()).map_err(Error::Write)
+    ///
+    /// This is the same as `save_to`
+    fn dump(&self, out: impl std::io::Write) -> Result<(), Error> {
+        self.save_to(out)
     }
 
     /// Ensures the directory exists and returns a `PathBuf` to it
@@ -171,6 +1308,909 @@ pub trait Configurable: Default + serde::Serialize + serde::de::DeserializeOwned
     /// Ensures the directory exists and returns a `PathBuf` to the
     /// configuration file inside of the directory
     fn path() -> Result<PathBuf, Error> {
-        Self::ensure_dir().map(|d| d.join(Self::NAME))
+        let path = Self::try_path()?;
+        Self::ensure_dir()?;
+        Ok(path)
+    }
+
+    /// Returns a `PathBuf` to the configuration file, without creating the directory
+    ///
+    /// An empty `name` is an `Error::InvalidName`
+    fn try_path() -> Result<PathBuf, Error> {
+        let name = Self::name();
+        if name.is_empty() {
+            return Err(Error::InvalidName(name.into_owned()));
+        }
+        Self::try_dir().map(|d| d.join(&*name))
+    }
+}
+
+/// The file name of `instance` (a profile, or a named instance) of `file`
+fn instance_file(file: &str, instance: &str) -> String {
+    match file.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{}.{}", stem, instance, ext),
+        None => format!("{}.{}", file, instance),
+    }
+}
+
+/// The instance which `name` is the file of, if it is named like one of `file`
+///
+/// This is the opposite of `instance_file`
+fn instance_of<'a>(name: &'a str, file: &str) -> Option<&'a str> {
+    let instance = match file.rsplit_once('.') {
+        Some((stem, ext)) => name
+            .strip_prefix(stem)?
+            .strip_prefix('.')?
+            .strip_suffix(ext)?
+            .strip_suffix('.')?,
+        None => name.strip_prefix(file)?.strip_prefix('.')?,
+    };
+    Some(instance).filter(|instance| valid_name(instance))
+}
+
+/// The file which the `SECRETS` of the named instance are saved in, or of the
+/// configuration if `name` is `None`
+pub(crate) fn secrets_path<T: Configurable>(name: Option<&str>) -> Result<PathBuf, Error> {
+    let file = match name {
+        Some(name) => instance_file(T::SECRETS_NAME, name),
+        None => T::SECRETS_NAME.to_string(),
+    };
+    T::try_dir().map(|dir| dir.join(file))
+}
+
+/// Whether `name` can be used as a named instance (or the id of an entry in a
+/// `Collection`), see `Configurable::named_path`
+pub(crate) fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', '/', '\\'])
+}
+
+/// Saves `this` to the file at `path`, which is `NAME` or a named instance
+///
+/// The `SECRETS` are saved to the file at `secrets`
+fn save_file<T: Configurable>(this: &T, path: &Path, secrets: &Path) -> Result<(), Error> {
+    save_file_with(this, path, secrets, Ok)
+}
+
+/// Saves `this` to the file at `path`, like `save_file`
+///
+/// `finish` is given the encoded file (without the `SECRETS`), and returns what is
+/// written (e.g. with a header)
+pub(crate) fn save_file_with<T: Configurable>(
+    this: &T,
+    path: &Path,
+    secrets: &Path,
+    finish: impl FnOnce(Vec<u8>) -> Result<Vec<u8>, Error>,
+) -> Result<(), Error> {
+    Env::once(|| {
+        // checked first, so the directory and the lock aren't created either
+        file::writable::<T>(path)?;
+        T::ensure_dir()?;
+        let _lock = file::lock(path)?;
+        let unlayered = unlayer(this, path, secrets)?;
+        write_file(unlayered.as_ref().unwrap_or(this), path, secrets, finish)?;
+        this.after_save(path);
+        Ok(())
+    })
+}
+
+/// Writes `this` to the file at `path`, and its `SECRETS` to the file at `secrets`
+///
+/// `finish` is given the encoded file, see `save_file_with`. This must be called
+/// while the file is locked
+fn write_file<T: Configurable>(
+    this: &T,
+    path: &Path,
+    secrets: &Path,
+    finish: impl FnOnce(Vec<u8>) -> Result<Vec<u8>, Error>,
+) -> Result<(), Error> {
+    if T::SECTION.is_none() && T::SECRETS.is_empty() {
+        // serialize before truncating the file, so a failure doesn't lose it
+        let data = finish(this.save_bytes()?)?;
+        return file::write::<T>(path, &data);
+    }
+
+    let value = toml::Value::try_from(this)?;
+    write_value::<T, T::Format>(value, path, secrets, |doc| {
+        T::Format::serialize_with(&doc, &T::save_options()).and_then(finish)
+    })
+}
+
+/// Writes `value` (an instance of `T`) to the file at `path`, and its `SECRETS` to the
+/// file at `secrets`
+///
+/// `encode` serializes the document of the file, which is `value` in its `SECTION`
+/// of the existing document in the `Format` `F`. This must be called while the file
+/// is locked
+pub(crate) fn write_value<T, F>(
+    mut value: toml::Value,
+    path: &Path,
+    secrets: &Path,
+    encode: impl FnOnce(toml::Value) -> Result<Vec<u8>, Error>,
+) -> Result<(), Error>
+where
+    T: Configurable,
+    F: Format,
+{
+    if !T::SECRETS.is_empty() {
+        let data =
+            encode_document::<T, TomlFormat>(secrets, value::take_paths(&mut value, T::SECRETS))?;
+        file::write::<T>(secrets, &data)?;
+    }
+    let data = encode(document::<T, F>(path, value)?)?;
+    file::write::<T>(path, &data)
+}
+
+/// Writes `this` to the file at `path` (like `save_bytes`), without the `SECRETS`
+/// unless `secrets` is set
+fn save_to_path<T: Configurable>(this: &T, path: &Path, secrets: bool) -> Result<(), Error> {
+    file::writable::<T>(path)?;
+    let data = match secrets || T::SECRETS.is_empty() {
+        true => this.save_bytes()?,
+        false => {
+            let mut value = toml::Value::try_from(this)?;
+            value::take_paths(&mut value, T::SECRETS);
+            let value = match T::SECTION {
+                Some(section) => {
+                    let doc = std::iter::once((section.to_string(), value)).collect();
+                    toml::Value::Table(doc)
+                }
+                None => value,
+            };
+            T::Format::serialize_with(&value, &T::save_options())?
+        }
+    };
+    file::replace(path, &data, T::FILE_MODE)
+}
+
+/// Loads `T`, or creates it with `init` when the file is missing (saving it, if `save` is set)
+fn load_state<T, F>(init: F, save: bool) -> Result<LoadState<T>, Error>
+where
+    T: Configurable,
+    F: FnOnce() -> Result<T, Error>,
+{
+    Env::once(|| load_state_once(init, save))
+}
+
+/// `load_state`, while the environment is only read once
+fn load_state_once<T, F>(init: F, save: bool) -> Result<LoadState<T>, Error>
+where
+    T: Configurable,
+    F: FnOnce() -> Result<T, Error>,
+{
+    let error = match T::load() {
+        Ok(this) => return Ok(LoadState::Loaded(this)),
+        Err(Error::NotFound(..)) => {
+            let this = init()?;
+            if save && !T::read_only() {
+                this.save()?;
+            }
+            return Ok(LoadState::Default(this));
+        }
+        Err(err) if err.errors().iter().any(unrecoverable) => return Err(err),
+        Err(error) => error,
+    };
+
+    if T::RECOVER {
+        for backup in T::backups()? {
+            if let Ok(value) = load_file(&backup, &secrets_path::<T>(None)?) {
+                return Ok(LoadState::Recovered {
+                    error,
+                    backup,
+                    value,
+                });
+            }
+        }
+    }
+    init().map(|default| LoadState::Invalid { error, default })
+}
+
+/// Whether the error is from something other than the contents of the file (e.g.
+/// the environment), so neither a default nor a backup should be used
+fn unrecoverable(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Io { .. }
+            | Error::NoHomeDirectory
+            | Error::TooLarge { .. }
+            | Error::UnknownFormat(..)
+            | Error::Env(..)
+    )
+}
+
+/// Loads `T` from the file at `path`, along with its secrets from the file at `secrets`
+///
+/// `path` doesn't have to be `NAME` (e.g. a backup). Then the environment is used for
+/// `T::EXPAND_VARS` and `T::ENV_OVERRIDES`, if they are set, then the active `Overrides`
+fn load_file<T: Configurable>(path: &Path, secrets: &Path) -> Result<T, Error> {
+    Env::once(|| {
+        read_file::<T>(path, secrets)
+            .and_then(apply_env)
+            .and_then(apply_overrides)
+    })
+}
+
+/// Uses the environment for `T::EXPAND_VARS` and `T::ENV_OVERRIDES`, if they are set
+fn apply_env<T: Configurable>(this: T) -> Result<T, Error> {
+    layer_env(this, T::EXPAND_VARS, T::ENV_OVERRIDES)
+}
+
+/// Expands the variables if `expand` is set, then applies the environment overrides
+/// if `overrides` is set
+fn layer_env<T: Configurable>(this: T, expand: bool, overrides: bool) -> Result<T, Error> {
+    if !expand && !overrides {
+        return Ok(this);
+    }
+    let mut value = toml::Value::try_from(&this)?;
+    if expand {
+        expand_env(&mut value)?;
+    }
+    if !overrides {
+        return value.try_into().map_err(Error::TomlRead);
+    }
+    let set = |value: &mut toml::Value, guess| env_overrides::<T>(value, guess);
+    deserialize_parsed(value, set, |value, _| {
+        value.try_into().map_err(Error::TomlRead)
+    })
+    .map(|(this, _)| this)
+}
+
+/// Deserializes `T` from `value`, after `set` sets the values which are parsed (see
+/// `value::set_parsed`), with `finish`
+///
+/// `set` is given whether to guess the types of the values which aren't there already.
+/// If `T` cannot be deserialized with the guesses, then they are strings instead
+pub(crate) fn deserialize_parsed<T, R>(
+    value: toml::Value,
+    set: impl Fn(&mut toml::Value, bool) -> Result<R, Error>,
+    finish: impl Fn(toml::Value, &R) -> Result<T, Error>,
+) -> Result<(T, R), Error> {
+    let mut guessed = value.clone();
+    let applied = set(&mut guessed, true)?;
+    match finish(guessed, &applied) {
+        Ok(this) => Ok((this, applied)),
+        Err(err @ Error::TomlRead(..)) => {
+            let mut value = value;
+            let applied = set(&mut value, false)?;
+            // the error with the guesses is kept, as it is the most likely one
+            let this = finish(value, &applied).map_err(|_| err)?;
+            Ok((this, applied))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Uses the active `Overrides` for `T`, if there are any
+fn apply_overrides<T: Configurable>(this: T) -> Result<T, Error> {
+    let overrides = Overrides::<T>::active();
+    if overrides.is_empty() {
+        return Ok(this);
+    }
+    let mut value = toml::Value::try_from(&this)?;
+    overrides::apply(&mut value, overrides);
+    value.try_into().map_err(Error::TomlRead)
+}
+
+/// Undoes the expanded variables (see `T::EXPAND_VARS`), the environment overrides
+/// (see `T::ENV_OVERRIDES`) and the active `Overrides` in `this`, which is about to be
+/// saved to the file at `path`, so their values aren't saved
+///
+/// The values which are still the same as when they were loaded (or only expanded,
+/// e.g. by `update`) are put back to the ones in the file, any others were changed so
+/// they are kept. Returns `None` if there is nothing to undo
+fn unlayer<T: Configurable>(this: &T, path: &Path, secrets: &Path) -> Result<Option<T>, Error> {
+    let env = T::ENV_OVERRIDES && !env::overrides(&T::application()).is_empty();
+    if !env && !T::EXPAND_VARS && Overrides::<T>::active().is_empty() {
+        return Ok(None);
+    }
+    // a file which is missing (or invalid) wasn't loaded, so nothing was layered over it
+    let read = match read_file::<T>(path, secrets) {
+        Ok(read) => read,
+        Err(..) => return Ok(None),
+    };
+    let raw = toml::Value::try_from(&read)?;
+    // neither was a file with a variable which is missing
+    let expanded = match layer_env(read, T::EXPAND_VARS, false) {
+        Ok(expanded) => expanded,
+        Err(..) => return Ok(None),
+    };
+    let only_expanded = toml::Value::try_from(&expanded)?;
+    let loaded = layer_env::<T>(expanded, false, env).and_then(apply_overrides)?;
+    let loaded = toml::Value::try_from(&loaded)?;
+    let mut value = toml::Value::try_from(this)?;
+    value::unlayer(&mut value, &loaded, Some(&raw));
+    if T::EXPAND_VARS {
+        // e.g. from `update`, which doesn't use the overrides
+        value::unlayer(&mut value, &only_expanded, Some(&raw));
+    }
+    value.try_into().map(Some).map_err(Error::TomlRead)
+}
+
+/// Expands the variables in `value`, see `EXPAND_VARS`
+pub(crate) fn expand_env(value: &mut toml::Value) -> Result<(), Error> {
+    value::expand_vars(value, &Env::vars()).map_err(|var| EnvError::Missing(var).into())
+}
+
+/// Applies the environment overrides for `T` to `value`, guessing the types of the
+/// values which aren't there if `guess` is set (see `value::set_parsed`)
+///
+/// Returns the dotted paths which were overridden, with their variables
+pub(crate) fn env_overrides<T: Configurable>(
+    value: &mut toml::Value,
+    guess: bool,
+) -> Result<Vec<(String, String)>, Error> {
+    let mut applied = vec![];
+    for (key, path, var) in env::overrides(&T::application()) {
+        match value::set_parsed(value, &path, &var, guess) {
+            Ok(path) => applied.push((path, key)),
+            Err(error) => {
+                let value = var;
+                return Err(EnvError::Invalid { key, value, error }.into());
+            }
+        }
+    }
+    Ok(applied)
+}
+
+fn read_file<T: Configurable>(path: &Path, secrets: &Path) -> Result<T, Error> {
+    let _lock = file::lock_shared(path)?;
+    // otherwise it is decoded into a value first, see `read_value`
+    let direct = T::SECRETS.is_empty()
+        && T::DEPRECATED_KEYS.is_empty()
+        && !T::FRAGMENTS
+        && T::EXTENDS.is_none();
+    #[cfg(feature = "include")]
+    let direct = direct && T::INCLUDE.is_none();
+    if direct && !file::checksummed::<T>() {
+        return T::load_from(file::open::<T>(path)?).map_err(Error::in_file(path));
+    }
+    let data = file::read::<T>(path)?;
+    if direct {
+        return T::load_bytes(&data).map_err(Error::in_file(path));
+    }
+    read_value::<T>(path, &data, Some(secrets))?
+        .try_into()
+        .map_err(Error::TomlRead)
+        .map_err(Error::in_file(path))
+}
+
+/// Reads the value for `T` from the file at `path`, returning `None` if it doesn't exist
+///
+/// This is a layer to be merged with others. The secrets and the `FRAGMENTS` are only
+/// used if `user` is set, as they belong to the user's file
+pub(crate) fn read_layer<T: Configurable>(
+    path: &Path,
+    user: bool,
+) -> Result<Option<toml::Value>, Error> {
+    let _lock = file::lock_shared(path)?;
+    let secrets = match user {
+        true => Some(secrets_path::<T>(None)?),
+        false => None,
+    };
+    match file::read::<T>(path).and_then(|data| read_value::<T>(path, &data, secrets.as_deref())) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::NotFound(..)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Decodes the value for `T` from `data` (the file at `path`) over the files it
+/// `EXTENDS`, merged with the files it `INCLUDE`s, its `FRAGMENTS`, then its secrets
+/// from the file at `secrets`
+///
+/// `secrets` is only given for the user's file, the other layers (e.g. the
+/// system-wide file) don't have secrets or `FRAGMENTS`
+///
+/// The `DEPRECATED_KEYS` are renamed in the value
+fn read_value<T: Configurable>(
+    path: &Path,
+    data: &[u8],
+    secrets: Option<&Path>,
+) -> Result<toml::Value, Error> {
+    let user = secrets.is_some();
+    // both files are read, so the errors of each can be reported together
+    let value = decode_document::<T, T::Format>(data)
+        .and_then(|doc| doc.ok_or_else(missing_section::<T>))
+        .map_err(Error::in_file(path))
+        .and_then(|value| resolve::<T>(value, &mut vec![path.to_owned()]));
+    let secrets = match (T::SECRETS, secrets) {
+        ([], _) | (_, None) => Ok(None),
+        (_, Some(secrets)) => match file::read::<T>(secrets) {
+            Ok(data) => decode_document::<T, TomlFormat>(&data).map_err(Error::in_file(secrets)),
+            Err(Error::NotFound(..)) => Ok(None),
+            Err(err) => Err(err),
+        },
+    };
+    let fragments = match T::FRAGMENTS && user {
+        true => read_fragments::<T>(),
+        false => Ok(vec![]),
+    };
+    let (mut value, secrets, fragments) = match (value, secrets, fragments) {
+        (Ok(value), Ok(secrets), Ok(fragments)) => (value, secrets, fragments),
+        (Err(err), secrets, fragments) => {
+            let rest = secrets.err().into_iter().chain(fragments.err());
+            return Err(Error::combine(err, rest));
+        }
+        (Ok(..), Err(err), fragments) => return Err(Error::combine(err, fragments.err())),
+        (Ok(..), Ok(..), Err(err)) => return Err(err),
+    };
+    for fragment in fragments {
+        value::merge(&mut value, fragment);
+    }
+    if let Some(secrets) = secrets {
+        value::merge(&mut value, secrets);
+    }
+    for &(key, replacement) in T::DEPRECATED_KEYS {
+        if value::rename(&mut value, key, replacement) {
+            let path = path.to_owned();
+            T::deprecated(&Deprecation {
+                key,
+                replacement,
+                path,
+            });
+        }
+    }
+    Ok(value)
+}
+
+/// Merges `value` over the files it `EXTENDS`, then merges the files it `INCLUDE`s over it
+///
+/// `chain` is the file of `value`, and the files which extend or include it
+fn resolve<T: Configurable>(
+    value: toml::Value,
+    chain: &mut Vec<PathBuf>,
+) -> Result<toml::Value, Error> {
+    let value = extend::<T>(value, chain)?;
+    #[cfg(feature = "include")]
+    let value = include::<T>(value, chain)?;
+    Ok(value)
+}
+
+/// Merges `value` over the files it names with the `EXTENDS` key of `T`
+///
+/// Each of them is resolved first, so they can extend and include other files.
+/// `chain` is the file of `value`, and the files which extend or include it
+fn extend<T: Configurable>(
+    mut value: toml::Value,
+    chain: &mut Vec<PathBuf>,
+) -> Result<toml::Value, Error> {
+    let path = chain.last().cloned().unwrap_or_default();
+    let names = match names(&mut value, T::EXTENDS, &path, "extends")? {
+        Some(names) => names,
+        None => return Ok(value),
+    };
+
+    let dir = dir_of::<T>(&path)?;
+    let mut base = toml::Value::Table(<_>::default());
+    for name in names {
+        let path = dir.join(name);
+        if chain.contains(&path) {
+            chain.push(path);
+            return Err(Error::Cycle(chain.clone()));
+        }
+        let data = file::read::<T>(&path)?;
+        let parent = decode_document::<T, T::Format>(&data)
+            .and_then(|doc| doc.ok_or_else(missing_section::<T>))
+            .map_err(Error::in_file(&path))?;
+        chain.push(path);
+        let parent = resolve::<T>(parent, chain)?;
+        chain.pop();
+        value::merge(&mut base, parent);
+    }
+    value::merge(&mut base, value);
+    Ok(base)
+}
+
+/// The directory which the files named by the file at `path` are relative to
+fn dir_of<T: Configurable>(path: &Path) -> Result<PathBuf, Error> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => Ok(dir.to_owned()),
+        _ => T::try_dir(),
+    }
+}
+
+/// Merges the files named by the `INCLUDE` key of `T` over `value`
+///
+/// Each of them is resolved first, so they can extend and include other files
+#[cfg(feature = "include")]
+fn include<T: Configurable>(
+    mut value: toml::Value,
+    chain: &mut Vec<PathBuf>,
+) -> Result<toml::Value, Error> {
+    let path = chain.last().cloned().unwrap_or_default();
+    let patterns = match names(&mut value, T::INCLUDE, &path, "includes")? {
+        Some(patterns) => patterns,
+        None => return Ok(value),
+    };
+
+    let dir = dir_of::<T>(&path)?;
+    let prefix = glob::Pattern::escape(&dir.to_string_lossy());
+    for pattern in patterns {
+        // so a file which starts matching is seen, e.g. `extra` for `extra/*.toml`
+        let literal = Path::new(&pattern)
+            .components()
+            .take_while(|part| !part.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect::<PathBuf>();
+        LoadToken::read(&dir.join(literal));
+        let full = format!("{}/{}", prefix, pattern);
+        let matches = glob::glob(&full).map_err(|err| {
+            let msg = format!("invalid pattern `{}`: {}", pattern, err.msg);
+            invalid_key(&path, &msg)
+        })?;
+        let mut paths = matches
+            .map(|path| {
+                path.map_err(|err| {
+                    let path = err.path().to_owned();
+                    Error::io(Op::Read, &path)(err.into())
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.is_file());
+        paths.sort();
+        for path in paths {
+            if chain.contains(&path) {
+                chain.push(path);
+                return Err(Error::Cycle(chain.clone()));
+            }
+            let data = file::read::<T>(&path)?;
+            let included = decode_document::<T, T::Format>(&data)
+                .and_then(|doc| doc.ok_or_else(missing_section::<T>))
+                .map_err(Error::in_file(&path))?;
+            chain.push(path);
+            let included = resolve::<T>(included, chain)?;
+            chain.pop();
+            value::merge(&mut value, included);
+        }
+    }
+    Ok(value)
+}
+
+/// Removes the `key` from `value`, which names other files (as a string, or an array
+/// of strings)
+///
+/// `what` is how the files are described in the error (e.g. `extends`)
+fn names(
+    value: &mut toml::Value,
+    key: Option<&str>,
+    path: &Path,
+    what: &str,
+) -> Result<Option<Vec<String>>, Error> {
+    let msg = format!(
+        "the files it {} must be a string, or an array of strings",
+        what
+    );
+    match key.and_then(|key| value.as_table_mut()?.remove(key)) {
+        None => Ok(None),
+        Some(toml::Value::String(name)) => Ok(Some(vec![name])),
+        Some(toml::Value::Array(names)) => names
+            .into_iter()
+            .map(|name| match name {
+                toml::Value::String(name) => Ok(name),
+                _ => Err(invalid_key(path, &msg)),
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+        Some(..) => Err(invalid_key(path, &msg)),
     }
 }
+
+/// The error for an `EXTENDS` or `INCLUDE` key which cannot be used
+fn invalid_key(path: &Path, msg: &str) -> Error {
+    use serde::de::Error as _;
+    Error::in_file(path)(Error::TomlRead(toml::de::Error::custom(msg)))
+}
+
+/// The files in the `FRAGMENTS` directory of `T` (e.g. `config.toml.d/`), in lexical order
+///
+/// These are the files with the same extension as `NAME`. A missing directory has no files
+pub(crate) fn fragments<T: Configurable>() -> Result<Vec<PathBuf>, Error> {
+    let dir = T::try_dir()?.join(format!("{}.d", T::name()));
+    LoadToken::read(&dir);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(Error::io(Op::Read, &dir)(err)),
+    };
+    let name = T::name();
+    let ext = Path::new(&*name).extension();
+    let mut paths = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == ext && path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Decodes the `FRAGMENTS` of `T`, skipping the ones without its `SECTION`
+///
+/// The errors of every fragment are returned together
+fn read_fragments<T: Configurable>() -> Result<Vec<toml::Value>, Error> {
+    let (mut values, mut errors) = (vec![], vec![]);
+    for path in fragments::<T>()? {
+        let value = file::read::<T>(&path)
+            .and_then(|data| decode_document::<T, T::Format>(&data))
+            .map_err(Error::in_file(&path));
+        match value {
+            Ok(value) => values.extend(value),
+            Err(err) => errors.push(err),
+        }
+    }
+    match Error::multiple(errors) {
+        Some(err) => Err(err),
+        None => Ok(values),
+    }
+}
+
+/// Deserializes `value`, where any of the `applied` dotted paths which aren't fields
+/// is an `Error::Override` of its assignment
+#[cfg(feature = "unknown-keys")]
+fn deserialize_known<T: Configurable>(
+    value: toml::Value,
+    applied: &[(String, String)],
+) -> Result<T, Error> {
+    let mut unknown = vec![];
+    let this = serde_ignored::deserialize(value, |key| unknown.push(dotted(&key)))?;
+    for (path, assignment) in applied {
+        let is_unknown = unknown.iter().any(|key| {
+            let nested = path.strip_prefix(key.as_str());
+            nested.is_some_and(|s| s.is_empty() || s.starts_with('.'))
+        });
+        if is_unknown {
+            let (assignment, error) =
+                (assignment.clone(), format!("`{}` is not a known key", path));
+            return Err(Error::Override { assignment, error });
+        }
+    }
+    Ok(this)
+}
+
+/// Deserializes `value`, without the `unknown-keys` feature the paths which aren't
+/// fields are ignored
+#[cfg(not(feature = "unknown-keys"))]
+fn deserialize_known<T: Configurable>(
+    value: toml::Value,
+    _applied: &[(String, String)],
+) -> Result<T, Error> {
+    value.try_into().map_err(Error::TomlRead)
+}
+
+/// The path of a key as dotted keys (e.g. `server.port`, or `servers.0.port`)
+#[cfg(feature = "unknown-keys")]
+fn dotted(mut path: &serde_ignored::Path<'_>) -> String {
+    use serde_ignored::Path;
+    let mut keys = vec![];
+    loop {
+        path = match path {
+            Path::Root => break,
+            Path::Seq { parent, index } => {
+                keys.push(index.to_string());
+                parent
+            }
+            Path::Map { parent, key } => {
+                keys.push(key.clone());
+                parent
+            }
+            Path::Some { parent }
+            | Path::NewtypeStruct { parent }
+            | Path::NewtypeVariant { parent } => parent,
+        }
+    }
+    keys.reverse();
+    keys.join(".")
+}
+
+/// Decodes the value for `T` from a document in the `Format` `F`, returning `None` if
+/// its `SECTION` is missing
+fn decode_document<T: Configurable, F: Format>(data: &[u8]) -> Result<Option<toml::Value>, Error> {
+    match T::SECTION {
+        Some(section) => {
+            let mut doc: toml::value::Table = F::deserialize(data)?;
+            Ok(doc.remove(section))
+        }
+        None => F::deserialize(data).map(Some),
+    }
+}
+
+/// Encodes the value for `T` as a document in the `Format` `F`
+///
+/// If `T` has a `SECTION`, then it is merged into the existing document at `path`
+fn encode_document<T: Configurable, F: Format>(
+    path: &Path,
+    value: toml::Value,
+) -> Result<Vec<u8>, Error> {
+    let doc = document::<T, F>(path, value)?;
+    F::serialize_with(&doc, &T::save_options())
+}
+
+/// The document for the value of `T`, which is the value in its `SECTION` of the
+/// existing document (in the `Format` `F`) at `path`
+fn document<T: Configurable, F: Format>(
+    path: &Path,
+    value: toml::Value,
+) -> Result<toml::Value, Error> {
+    let section = match T::SECTION {
+        Some(section) => section,
+        None => return Ok(value),
+    };
+    let mut doc: toml::value::Table = match fs::read(path) {
+        Ok(data) => F::deserialize(&data).map_err(Error::in_file(path))?,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => <_>::default(),
+        Err(err) => return Err(Error::io(Op::Read, path)(err)),
+    };
+    doc.insert(section.to_string(), value);
+    Ok(toml::Value::Table(doc))
+}
+
+/// Removes the file at `path`, or only the `SECTION` for `T` inside of it
+fn remove_document<T: Configurable, F: Format>(path: &Path) -> Result<(), Error> {
+    let section = match T::SECTION {
+        Some(section) => section,
+        None => return file::remove::<T>(path),
+    };
+    let mut doc: toml::value::Table = match fs::read(path) {
+        Ok(data) => F::deserialize(&data).map_err(Error::in_file(path))?,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(Error::io(Op::Read, path)(err)),
+    };
+    if doc.remove(section).is_none() {
+        return Ok(());
+    }
+    if doc.is_empty() {
+        return file::remove::<T>(path);
+    }
+    let data = F::serialize_with(&doc, &T::save_options())?;
+    file::write::<T>(path, &data)
+}
+
+fn missing_section<T: Configurable>() -> Error {
+    let err = format!("section `{}` was not found", T::SECTION.unwrap_or_default());
+    Error::Read(std::io::Error::new(std::io::ErrorKind::NotFound, err))
+}
+
+/// A `Format` which is chosen by the extension of `Configurable::NAME`
+///
+/// * `.toml` uses `TomlFormat`
+/// * `.json` uses `JsonFormat` (requires the `json` feature)
+/// * `.json5` uses `Json5Format` (requires the `json5` feature)
+/// * `.yaml` and `.yml` use `YamlFormat` (requires the `yaml` feature)
+/// * `.ron` uses `RonFormat` (requires the `ron` feature)
+/// * `.bin` and `.bincode` use `BincodeFormat` (requires the `bincode` feature)
+/// * `.msgpack` and `.mpk` use `MsgPackFormat` (requires the `msgpack` feature)
+/// * `.cbor` uses `CborFormat` (requires the `cbor` feature)
+/// * `.ini` uses `IniFormat` (requires the `ini` feature)
+/// * `.kdl` uses `KdlFormat` (requires the `kdl` feature)
+/// * `.xml` uses `XmlFormat` (requires the `xml` feature)
+///
+/// Any other extension produces an `Error::UnknownFormat`
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use configurable::{Configurable, Detect};
+/// # #[derive(Default, Serialize, Deserialize)]
+/// # struct Foo;
+/// impl Configurable for Foo {
+///     const ORGANIZATION: &'static str = "some_org";
+///     const APPLICATION: &'static str = "foobar";
+///     const NAME: &'static str = "config.toml";
+///     // this will use `TomlFormat`
+///     type Format = Detect<Self>;
+/// }
+/// ```
+pub struct Detect<C>(PhantomData<C>);
+
+impl<C: Configurable> Format for Detect<C> {
+    fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        Self::serialize_with(value, &SaveOptions::default())
+    }
+
+    fn serialize_with<T: serde::Serialize>(
+        value: &T,
+        options: &SaveOptions,
+    ) -> Result<Vec<u8>, Error> {
+        detect_serialize_with(&C::name(), value, options)
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        detect_deserialize(&C::name(), data)
+    }
+
+    fn deserialize_reader<T: serde::de::DeserializeOwned>(
+        reader: impl std::io::Read,
+    ) -> Result<T, Error> {
+        match detect_extension(&C::name())?.as_str() {
+            "toml" => TomlFormat::deserialize_reader(reader),
+            #[cfg(feature = "json")]
+            "json" => JsonFormat::deserialize_reader(reader),
+            #[cfg(feature = "json5")]
+            "json5" => Json5Format::deserialize_reader(reader),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => YamlFormat::deserialize_reader(reader),
+            #[cfg(feature = "ron")]
+            "ron" => RonFormat::deserialize_reader(reader),
+            #[cfg(feature = "bincode")]
+            "bin" | "bincode" => BincodeFormat::deserialize_reader(reader),
+            #[cfg(feature = "msgpack")]
+            "msgpack" | "mpk" => MsgPackFormat::deserialize_reader(reader),
+            #[cfg(feature = "cbor")]
+            "cbor" => CborFormat::deserialize_reader(reader),
+            #[cfg(feature = "ini")]
+            "ini" => IniFormat::deserialize_reader(reader),
+            #[cfg(feature = "kdl")]
+            "kdl" => KdlFormat::deserialize_reader(reader),
+            #[cfg(feature = "xml")]
+            "xml" => XmlFormat::deserialize_reader(reader),
+            _ => Err(Error::UnknownFormat(C::name().into_owned())),
+        }
+    }
+}
+
+/// Serializes `value` in the `Format` for the extension of `name`, see `Detect`
+pub(crate) fn detect_serialize_with<T: serde::Serialize>(
+    name: &str,
+    value: &T,
+    options: &SaveOptions,
+) -> Result<Vec<u8>, Error> {
+    match detect_extension(name)?.as_str() {
+        "toml" => TomlFormat::serialize_with(value, options),
+        #[cfg(feature = "json")]
+        "json" => JsonFormat::serialize_with(value, options),
+        #[cfg(feature = "json5")]
+        "json5" => Json5Format::serialize_with(value, options),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => YamlFormat::serialize_with(value, options),
+        #[cfg(feature = "ron")]
+        "ron" => RonFormat::serialize_with(value, options),
+        #[cfg(feature = "bincode")]
+        "bin" | "bincode" => BincodeFormat::serialize_with(value, options),
+        #[cfg(feature = "msgpack")]
+        "msgpack" | "mpk" => MsgPackFormat::serialize_with(value, options),
+        #[cfg(feature = "cbor")]
+        "cbor" => CborFormat::serialize_with(value, options),
+        #[cfg(feature = "ini")]
+        "ini" => IniFormat::serialize_with(value, options),
+        #[cfg(feature = "kdl")]
+        "kdl" => KdlFormat::serialize_with(value, options),
+        #[cfg(feature = "xml")]
+        "xml" => XmlFormat::serialize_with(value, options),
+        _ => Err(Error::UnknownFormat(name.to_string())),
+    }
+}
+
+/// Deserializes `data` in the `Format` for the extension of `name`, see `Detect`
+pub(crate) fn detect_deserialize<T: serde::de::DeserializeOwned>(
+    name: &str,
+    data: &[u8],
+) -> Result<T, Error> {
+    match detect_extension(name)?.as_str() {
+        "toml" => TomlFormat::deserialize(data),
+        #[cfg(feature = "json")]
+        "json" => JsonFormat::deserialize(data),
+        #[cfg(feature = "json5")]
+        "json5" => Json5Format::deserialize(data),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => YamlFormat::deserialize(data),
+        #[cfg(feature = "ron")]
+        "ron" => RonFormat::deserialize(data),
+        #[cfg(feature = "bincode")]
+        "bin" | "bincode" => BincodeFormat::deserialize(data),
+        #[cfg(feature = "msgpack")]
+        "msgpack" | "mpk" => MsgPackFormat::deserialize(data),
+        #[cfg(feature = "cbor")]
+        "cbor" => CborFormat::deserialize(data),
+        #[cfg(feature = "ini")]
+        "ini" => IniFormat::deserialize(data),
+        #[cfg(feature = "kdl")]
+        "kdl" => KdlFormat::deserialize(data),
+        #[cfg(feature = "xml")]
+        "xml" => XmlFormat::deserialize(data),
+        _ => Err(Error::UnknownFormat(name.to_string())),
+    }
+}
+
+fn detect_extension(name: &str) -> Result<String, Error> {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .ok_or_else(|| Error::UnknownFormat(name.to_string()))
+}
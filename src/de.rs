@@ -0,0 +1,185 @@
+use serde::de::{
+    self,
+    value::{Error, MapDeserializer, SeqDeserializer},
+    IntoDeserializer, Unexpected, Visitor,
+};
+
+/// A tree of untyped strings which deserializes by parsing its leaves
+///
+/// This is used for sources where the values are untyped (e.g. INI files, or
+/// environment variables)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Leaf(String),
+    #[cfg_attr(not(feature = "kdl"), allow(dead_code))]
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Gets, or creates, the map at `path`
+    pub(crate) fn map_at(&mut self, path: &[String]) -> Option<&mut Vec<(String, Node)>> {
+        let mut node = self;
+        for part in path {
+            let map = match node {
+                Node::Map(map) => map,
+                _ => return None,
+            };
+            let pos = match map.iter().position(|(k, _)| k == part) {
+                Some(pos) => pos,
+                None => {
+                    map.push((part.clone(), Node::Map(vec![])));
+                    map.len() - 1
+                }
+            };
+            node = &mut map[pos].1;
+        }
+        match node {
+            Node::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! parse {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self {
+                    Node::Leaf(s) => {
+                        let value = s.trim().parse().map_err(|_| {
+                            de::Error::invalid_value(Unexpected::Str(&s), &visitor)
+                        })?;
+                        visitor.$visit(value)
+                    }
+                    Node::Seq(mut list) if list.len() == 1 => list.remove(0).$method(visitor),
+                    node => node.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Node::Leaf(s) => visitor.visit_string(s),
+            Node::Seq(mut list) if list.len() == 1 => list.remove(0).deserialize_any(visitor),
+            Node::Seq(list) => visitor.visit_seq(SeqDeserializer::new(list.into_iter())),
+            Node::Map(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Node::Leaf(s) => match s.trim().to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => visitor.visit_bool(true),
+                "false" | "no" | "off" | "0" => visitor.visit_bool(false),
+                _ => Err(de::Error::invalid_value(Unexpected::Str(&s), &visitor)),
+            },
+            Node::Seq(mut list) if list.len() == 1 => list.remove(0).deserialize_bool(visitor),
+            node => node.deserialize_any(visitor),
+        }
+    }
+
+    parse! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Node::Leaf(s) => {
+                let items = s
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| Node::Leaf(s.to_string()));
+                visitor.visit_seq(SeqDeserializer::new(items))
+            }
+            Node::Seq(list) => visitor.visit_seq(SeqDeserializer::new(list.into_iter())),
+            node => node.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Node::Leaf(s) => visitor.visit_enum(s.into_deserializer()),
+            Node::Seq(mut list) if list.len() == 1 => {
+                list.remove(0).deserialize_enum(name, variants, visitor)
+            }
+            node => node.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string bytes byte_buf map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
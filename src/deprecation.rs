@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+
+/// A deprecated key which was used by a file, see `Configurable::DEPRECATED_KEYS`
+///
+/// This displays as a notice which can be shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    pub(crate) key: &'static str,
+    pub(crate) replacement: &'static str,
+    pub(crate) path: PathBuf,
+}
+
+impl Deprecation {
+    /// The deprecated key (e.g. `server.host`)
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    /// The key it was renamed to (e.g. `server.address`)
+    pub fn replacement(&self) -> &'static str {
+        self.replacement
+    }
+
+    /// The file which used the deprecated key
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl std::fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` in {} is deprecated, use `{}` instead",
+            self.key,
+            self.path.display(),
+            self.replacement
+        )
+    }
+}
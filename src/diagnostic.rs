@@ -0,0 +1,110 @@
+use super::*;
+
+use miette::{Diagnostic, LabeledSpan, SourceCode};
+use std::path::Path;
+
+/// Errors are rendered by `miette`, with the part of the file which cannot be parsed
+///
+/// ```
+/// # use configurable::{Error, Format, TomlFormat};
+/// let err = Error::Parse {
+///     path: "config.toml".into(),
+///     source: Box::new(TomlFormat::deserialize::<u32>(b"a = [").unwrap_err()),
+///     contents: Some("a = [".into()),
+/// };
+/// let labels = miette::Diagnostic::labels(&err).unwrap().collect::<Vec<_>>();
+/// assert_eq!(labels[0].offset(), 5);
+/// ```
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let code = match self.kind() {
+            ErrorKind::NoHomeDirectory => "no_home_directory",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Io => "io",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Serialize => "serialize",
+            ErrorKind::Corrupt => "corrupt",
+            ErrorKind::TooLarge => "too_large",
+            ErrorKind::UnknownFormat => "unknown_format",
+            ErrorKind::Format => "format",
+            ErrorKind::Env => "env",
+            ErrorKind::Override => "override",
+            ErrorKind::ReadOnly => "read_only",
+            ErrorKind::InvalidName => "invalid_name",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Multiple => "multiple",
+        };
+        Some(Box::new(format!("configurable::{}", code)))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        match self {
+            Error::Parse {
+                contents: Some(contents),
+                ..
+            } => Some(contents as &dyn SourceCode),
+            _ => None,
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let (source, contents) = match self {
+            Error::Parse {
+                source,
+                contents: Some(contents),
+                ..
+            } => (source, contents),
+            _ => return None,
+        };
+        let label = match &**source {
+            Error::Field { field, .. } => format!("in field `{}`", field),
+            _ => "here".to_string(),
+        };
+        let span = LabeledSpan::at_offset(offset(source, contents)?, label);
+        Some(Box::new(std::iter::once(span)))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        match self {
+            Error::Multiple(errors) => {
+                Some(Box::new(errors.iter().map(|err| err as &dyn Diagnostic)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The contents of the file at `path`, if the error has a position in it
+///
+/// This is only read when an error is produced, so the file is read again
+pub(crate) fn contents(err: &Error, path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    offset(err, &contents).map(|_| contents)
+}
+
+/// The byte offset in `contents` where the error is
+fn offset(err: &Error, contents: &str) -> Option<usize> {
+    let (line, col) = match err {
+        Error::Field { source, .. } => return offset(source, contents),
+        Error::TomlRead(err) => err.line_col()?,
+        #[cfg(feature = "json")]
+        Error::JsonRead(err) if err.line() > 0 => (err.line() - 1, err.column().saturating_sub(1)),
+        #[cfg(feature = "yaml")]
+        Error::YamlRead(err) => return err.location().map(|loc| loc.index().min(contents.len())),
+        #[cfg(feature = "ron")]
+        Error::RonRead(err) if err.position.line > 0 => {
+            (err.position.line - 1, err.position.col.saturating_sub(1))
+        }
+        _ => return None,
+    };
+    let start = contents
+        .split_inclusive('\n')
+        .take(line)
+        .map(str::len)
+        .sum::<usize>();
+    let mut offset = (start + col).min(contents.len());
+    while !contents.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    Some(offset)
+}
@@ -0,0 +1,81 @@
+use toml::Value;
+
+/// A value which is different between two configurations, see `Configurable::diff`
+///
+/// This displays as e.g. `server.port: 80 -> 8080`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedKey {
+    key: String,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl ChangedKey {
+    /// The dotted key of the value (e.g. `server.port`)
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The old value, or `None` if it was added
+    pub fn before(&self) -> Option<&Value> {
+        self.before.as_ref()
+    }
+
+    /// The new value, or `None` if it was removed
+    pub fn after(&self) -> Option<&Value> {
+        self.after.as_ref()
+    }
+}
+
+impl std::fmt::Display for ChangedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.before, &self.after) {
+            (Some(old), Some(new)) => write!(f, "{}: {} -> {}", self.key, old, new),
+            (Some(old), None) => write!(f, "{}: {} was removed", self.key, old),
+            (None, Some(new)) => write!(f, "{}: {} was added", self.key, new),
+            (None, None) => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// The values which are different between `old` and `new`
+///
+/// Tables are compared key by key, and any other values (including arrays) as a whole
+pub(crate) fn diff(old: &Value, new: &Value) -> Vec<ChangedKey> {
+    let mut out = vec![];
+    walk("", Some(old), Some(new), &mut out);
+    out
+}
+
+fn walk(prefix: &str, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<ChangedKey>) {
+    let key = |key: &str| match prefix {
+        "" => key.to_string(),
+        prefix => format!("{}.{}", prefix, key),
+    };
+    match (old, new) {
+        (Some(Value::Table(old)), Some(Value::Table(new))) => {
+            for (k, value) in old {
+                walk(&key(k), Some(value), new.get(k), out);
+            }
+            for (k, value) in new.iter().filter(|(k, _)| !old.contains_key(*k)) {
+                walk(&key(k), None, Some(value), out);
+            }
+        }
+        (Some(Value::Table(old)), None) if !old.is_empty() => {
+            for (k, value) in old {
+                walk(&key(k), Some(value), None, out);
+            }
+        }
+        (None, Some(Value::Table(new))) if !new.is_empty() => {
+            for (k, value) in new {
+                walk(&key(k), None, Some(value), out);
+            }
+        }
+        (old, new) if old != new => out.push(ChangedKey {
+            key: prefix.to_string(),
+            before: old.cloned(),
+            after: new.cloned(),
+        }),
+        _ => {}
+    }
+}
@@ -0,0 +1,175 @@
+use super::*;
+
+use serde::{de::DeserializeOwned, Serialize};
+use toml::{value::Table, Value};
+
+/// A configuration without a schema, whose values are accessed by their dotted keys
+///
+/// This is for things like a `config get`/`config set` command, which can work with
+/// any key. Use `load` and `save` for the file of a `Configurable`, which checks the
+/// values are still valid for it before saving
+///
+/// ```
+/// # use configurable::Document;
+/// let mut doc = Document::parse("[server]\nport = 80\nhosts = []").unwrap();
+/// assert_eq!(doc.get::<u16>("server.port").unwrap(), Some(80));
+///
+/// doc.set("server.port", 8080).unwrap();
+/// // this is parsed as the type of the value, so a missing value would be a string
+/// doc.set_parsed("server.hosts", "a,b").unwrap();
+/// assert_eq!(doc.get::<Vec<String>>("server.hosts").unwrap(), Some(vec!["a".into(), "b".into()]));
+/// assert_eq!(doc.to_string(), "[server]\nport = 8080\nhosts = [\"a\", \"b\"]\n");
+///
+/// let err = doc.set("server.port.number", 1).unwrap_err();
+/// assert_eq!(err.to_string(), "cannot apply `server.port.number=1`: `port` is not a table");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    value: Value,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Document {
+    /// Creates an empty document
+    pub fn new() -> Self {
+        Self {
+            value: Value::Table(Table::new()),
+        }
+    }
+
+    /// Parses a toml document
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        Ok(Self {
+            value: toml::from_str(s)?,
+        })
+    }
+
+    /// Creates a document from the values of `value`
+    pub fn from_value<T: Serialize>(value: &T) -> Result<Self, Error> {
+        Ok(Self {
+            value: Value::try_from(value)?,
+        })
+    }
+
+    /// Loads the configuration of `T` as a document, see `Configurable::load`
+    pub fn load<T: Configurable>() -> Result<Self, Error> {
+        Self::from_value(&T::load()?)
+    }
+
+    /// Saves the document as the configuration of `T`, see `Configurable::save`
+    ///
+    /// The document has to be a valid `T`
+    pub fn save<T: Configurable>(&self) -> Result<(), Error> {
+        self.deserialize::<T>()?.save()
+    }
+
+    /// Deserializes the document
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(self.value.clone().try_into()?)
+    }
+
+    /// The value at the dotted `key` (e.g. `server.port`), if there is one
+    ///
+    /// A value which isn't a `T` is an `Error::Field`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, Error> {
+        let value = match self.get_value(key) {
+            Some(value) => value.clone(),
+            None => return Ok(None),
+        };
+        value.try_into().map(Some).map_err(|err| Error::Field {
+            field: key.to_string(),
+            source: Box::new(Error::TomlRead(err)),
+        })
+    }
+
+    /// The toml value at the dotted `key`, if there is one
+    pub fn get_value(&self, key: &str) -> Option<&Value> {
+        key.split('.')
+            .try_fold(&self.value, |value, part| value.get(part))
+    }
+
+    /// Sets the value at the dotted `key`, creating any missing tables
+    ///
+    /// A key inside of a value which isn't a table is an `Error::Override`
+    pub fn set(&mut self, key: &str, value: impl Serialize) -> Result<(), Error> {
+        let value = Value::try_from(value)?;
+        let error = |error| Error::Override {
+            assignment: format!("{}={}", key, value),
+            error,
+        };
+        let path = key.split('.').collect::<Vec<_>>();
+        let (last, parents) = path.split_last().expect("split has one part");
+        let table = parents
+            .iter()
+            .try_fold(&mut self.value, |value, part| match value {
+                Value::Table(table) => Ok(table
+                    .entry(part.to_string())
+                    .or_insert_with(|| Value::Table(Table::new()))),
+                _ => Err(format!("`{}` is not a table", part)),
+            })
+            .and_then(|value| match value {
+                Value::Table(table) => Ok(table),
+                _ => Err(format!(
+                    "`{}` is not a table",
+                    parents.last().unwrap_or(last)
+                )),
+            })
+            .map_err(error)?;
+        table.insert(last.to_string(), value);
+        Ok(())
+    }
+
+    /// Sets the value at the dotted `key` to `s`, parsed as the type of the value
+    /// which is already there
+    ///
+    /// This parses values like `Configurable::apply_overrides`, so it can be used with
+    /// the arguments of a `config set` command
+    pub fn set_parsed(&mut self, key: &str, s: &str) -> Result<(), Error> {
+        let path = key.split('.').map(str::to_string).collect::<Vec<_>>();
+        value::set_parsed(&mut self.value, &path, s, true)
+            .map(drop)
+            .map_err(|error| Error::Override {
+                assignment: format!("{}={}", key, s),
+                error,
+            })
+    }
+
+    /// Removes the value at the dotted `key`, returning it
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        value::remove(&mut self.value, &key.split('.').collect::<Vec<_>>())
+    }
+
+    /// The dotted keys of every value in the document
+    pub fn keys(&self) -> Vec<String> {
+        value::leaves(&self.value)
+    }
+
+    /// The document as a toml value
+    pub fn as_value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Consumes the document, returning it as a toml value
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+impl From<Value> for Document {
+    fn from(value: Value) -> Self {
+        Self { value }
+    }
+}
+
+/// The document as toml
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = toml::to_string(&self.value).map_err(|_| std::fmt::Error)?;
+        f.write_str(&s)
+    }
+}
@@ -0,0 +1,200 @@
+use super::*;
+
+/// A Configurable type which describes its fields
+///
+/// The descriptions are written as comments above the fields, along with
+/// their default values
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use configurable::{Configurable, Documented, TomlFormat};
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Foo { name: String, server: Server }
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Server { port: u16 }
+///
+/// impl Documented for Foo {
+///     const DOCS: &'static [(&'static str, &'static str)] = &[
+///         ("name", "the name of the bot"),
+///         ("server.port", "port to listen on"),
+///     ];
+/// }
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "foobar";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+///
+/// let doc = Foo::default().to_documented_string().unwrap();
+/// assert!(doc.starts_with("# the name of the bot\n# default: \"\"\nname = ''\n"));
+/// assert!(doc.contains("# port to listen on\n# default: 0\nport = 0\n"));
+/// ```
+pub trait Documented: Configurable {
+    /// Descriptions of the fields, as `(dotted.key, description)` pairs
+    const DOCS: &'static [(&'static str, &'static str)];
+
+    /// Renders this instance as TOML, with the documented fields preceded by comments
+    ///
+    /// The defaults in the comments are from `load_embedded`
+    fn to_documented_string(&self) -> Result<String, Error> {
+        render::<Self>(&toml::Value::try_from(self)?, None)
+    }
+
+    /// Writes the default configuration (see `load_embedded`), with its documentation,
+    /// to the file
+    ///
+    /// Like `save`, the `SECRETS` are written to `SECRETS_NAME` and a `SECTION` is
+    /// merged into the rest of the file
+    fn write_default_with_docs() -> Result<(), Error> {
+        let (path, secrets) = (Self::try_path()?, configurable::secrets_path::<Self>(None)?);
+        // checked first, so the directory and the lock aren't created either
+        crate::file::writable::<Self>(&path)?;
+        Self::ensure_dir()?;
+        let _lock = crate::file::lock(&path)?;
+        let this = Self::load_embedded()?;
+        let value = toml::Value::try_from(&this)?;
+        configurable::write_value::<Self, TomlFormat>(value, &path, &secrets, |doc| {
+            render::<Self>(&doc, Self::SECTION).map(String::into_bytes)
+        })?;
+        this.after_save(&path);
+        Ok(())
+    }
+}
+
+/// Renders the document `doc` as TOML, with the documented fields of `T` preceded by
+/// comments
+///
+/// With a `section`, only the keys inside of it are the fields of `T`
+fn render<T: Documented>(doc: &toml::Value, section: Option<&str>) -> Result<String, Error> {
+    let defaults = toml::Value::try_from(T::load_embedded()?)?;
+    let body = toml::to_string_pretty(doc)?;
+
+    let mut out = String::with_capacity(body.len());
+    let mut table = String::new();
+    let mut scanner = Scanner::default();
+    for line in body.lines() {
+        // lines inside of multi-line strings and arrays aren't keys
+        if scanner.at_top() {
+            if let Some(header) = table_header(line) {
+                table = header.to_string();
+            } else if let Some(key) = line_key(line) {
+                let key = match table.is_empty() {
+                    true => key.to_string(),
+                    false => format!("{}.{}", table, key),
+                };
+                let key = match section {
+                    Some(section) => key
+                        .strip_prefix(section)
+                        .and_then(|key| key.strip_prefix('.'))
+                        .map(str::to_string),
+                    None => Some(key),
+                };
+                if let Some(key) = key {
+                    if let Some((_, doc)) = T::DOCS.iter().find(|(k, _)| *k == key) {
+                        for doc in doc.lines() {
+                            out.push_str(&format!("# {}\n", doc));
+                        }
+                        if let Some(value) = lookup(&defaults, &key) {
+                            out.push_str(&format!("# default: {}\n", value));
+                        }
+                    }
+                }
+            }
+        }
+        scanner.scan(line);
+        out.push_str(line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Follows the lines of a TOML document, to tell which of them start at the top
+/// level of a table
+#[derive(Default)]
+struct Scanner {
+    /// The delimiter of the multi-line string which the line ended in
+    multiline: Option<&'static str>,
+    /// How many arrays (or inline tables) the line ended in
+    depth: usize,
+}
+
+impl Scanner {
+    /// Whether the next line is at the top level of a table, rather than inside of
+    /// a multi-line string or array
+    fn at_top(&self) -> bool {
+        self.multiline.is_none() && self.depth == 0
+    }
+
+    fn scan(&mut self, line: &str) {
+        const DELIMITERS: [&str; 2] = ["\"\"\"", "'''"];
+
+        let mut rest = line.chars();
+        loop {
+            let text = rest.as_str();
+            if let Some(delimiter) = self.multiline {
+                if let Some(text) = text.strip_prefix(delimiter) {
+                    self.multiline = None;
+                    rest = text.chars();
+                    continue;
+                }
+                match rest.next() {
+                    // a basic string can escape its delimiter
+                    Some('\\') if delimiter == DELIMITERS[0] => {
+                        rest.next();
+                    }
+                    Some(..) => {}
+                    None => return,
+                }
+                continue;
+            }
+            let opened = DELIMITERS
+                .iter()
+                .find_map(|&d| Some((d, text.strip_prefix(d)?)));
+            if let Some((delimiter, text)) = opened {
+                self.multiline = Some(delimiter);
+                rest = text.chars();
+                continue;
+            }
+            match rest.next() {
+                None | Some('#') => return,
+                Some(quote @ ('"' | '\'')) => loop {
+                    match rest.next() {
+                        Some('\\') if quote == '"' => {
+                            rest.next();
+                        }
+                        Some(c) if c == quote => break,
+                        Some(..) => {}
+                        None => return,
+                    }
+                },
+                Some('[' | '{') => self.depth += 1,
+                Some(']' | '}') => self.depth = self.depth.saturating_sub(1),
+                Some(..) => {}
+            }
+        }
+    }
+}
+
+fn table_header(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let line = line
+        .strip_prefix("[[")
+        .and_then(|s| s.strip_suffix("]]"))
+        .or_else(|| line.strip_prefix('[').and_then(|s| s.strip_suffix(']')))?;
+    Some(line.trim())
+}
+
+fn line_key(line: &str) -> Option<&str> {
+    if line.starts_with(char::is_whitespace) || line.starts_with('#') {
+        return None;
+    }
+    let (key, _) = line.split_once(" = ")?;
+    Some(key.trim().trim_matches('"'))
+}
+
+fn lookup<'a>(value: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
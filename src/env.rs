@@ -1,39 +1,1311 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::de::Node;
+use crate::EnvError;
+
+/// How many parent directories `Env::env` searches for the .env file
+const SEARCH_DEPTH: usize = 5;
+
+thread_local! {
+    /// The variables of the operation running on this thread, see `Env::once`
+    static VARS: RefCell<Option<Option<HashMap<String, String>>>> = const { RefCell::new(None) };
+}
 
 /// Environment var loader which can be overridden by a .env file
 pub struct Env;
 impl Env {
     /// Get the `key` from the `env`
     ///
-    /// This is overridden by the .env file
+    /// This is overridden by the nearest .env file (see `find`), which is searched
+    /// for in up to 5 parent directories
     pub fn env(key: &str) -> Option<String> {
-        Self::load(".env")
-            .remove(key)
-            .or_else(|| env::var(key).ok())
+        let path = Self::find(".env", SEARCH_DEPTH).unwrap_or_else(|| ".env".into());
+        Self::load(path).remove(key).or_else(|| env::var(key).ok())
     }
 
-    /// Override the environment from the .env file
+    /// Get the `key` from the `env` (see `env`), and parse it
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// std::env::set_var("CONFIGURABLE_GET_PORT", "8080");
+    /// assert_eq!(Env::get::<u16>("CONFIGURABLE_GET_PORT").unwrap(), 8080);
+    /// assert!(Env::get::<u16>("CONFIGURABLE_GET_MISSING").is_err());
+    /// ```
+    pub fn get<T>(key: &str) -> Result<T, EnvError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        get_with(key, Self::env(key), parse_from_str)
+    }
+
+    /// Get the `key` from the `env` as a `bool`
+    ///
+    /// This is `true`, `yes`, `on` or `1` and `false`, `no`, `off` or `0`, ignoring case
+    pub fn get_bool(key: &str) -> Result<bool, EnvError> {
+        get_with(key, Self::env(key), parse_bool)
+    }
+
+    /// Get the `key` from the `env` as a `Duration`
+    ///
+    /// This is a number followed by `ms`, `s`, `m`, `h` or `d`, e.g. `1.5s`. A number
+    /// by itself is in seconds
+    pub fn get_duration(key: &str) -> Result<Duration, EnvError> {
+        get_with(key, Self::env(key), parse_duration)
+    }
+
+    /// Get the `key` from the `env` as a path
+    pub fn get_path(key: &str) -> Result<PathBuf, EnvError> {
+        get_with(key, Self::env(key), |value| Ok(value.into()))
+    }
+
+    /// Get all of the `keys` from the `env` (see `env`)
+    ///
+    /// If any of them are missing, the error lists all of them
+    ///
+    /// ```
+    /// # use configurable::{Env, EnvError};
+    /// std::env::set_var("CONFIGURABLE_REQUIRE_TOKEN", "hunter2");
+    ///
+    /// let vars = Env::require(["CONFIGURABLE_REQUIRE_TOKEN"]).unwrap();
+    /// assert_eq!(vars["CONFIGURABLE_REQUIRE_TOKEN"], "hunter2");
+    ///
+    /// let err = Env::require(["CONFIGURABLE_REQUIRE_A", "CONFIGURABLE_REQUIRE_B"]).unwrap_err();
+    /// assert!(matches!(err, EnvError::Required(keys) if keys.len() == 2));
+    /// ```
+    pub fn require<I, K>(keys: I) -> Result<HashMap<String, String>, EnvError>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        let mut vars = Self::vars();
+        let (mut found, mut missing) = (HashMap::new(), vec![]);
+        for key in keys {
+            let key = key.as_ref();
+            match vars.remove(key) {
+                Some(value) => {
+                    found.insert(key.to_string(), value);
+                }
+                None if !found.contains_key(key) => missing.push(key.to_string()),
+                None => {}
+            }
+        }
+        match missing.is_empty() {
+            true => Ok(found),
+            false => Err(EnvError::Required(missing)),
+        }
+    }
+
+    /// Set the `vars` in the environment, until the guard is dropped
+    ///
+    /// The variables are then restored to what they were before, even if the thread
+    /// panicked. Only one thread at a time can hold these guards (others wait for it),
+    /// but they can be nested within the thread and dropped in any order. Each guard
+    /// restores the variables it set to what they were when it was created. This is
+    /// meant for tests
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// let port = Env::scoped([("CONFIGURABLE_SCOPED_PORT", "1234")])
+    ///     .run(|| Env::get::<u16>("CONFIGURABLE_SCOPED_PORT"));
+    /// assert_eq!(port.unwrap(), 1234);
+    /// assert!(std::env::var("CONFIGURABLE_SCOPED_PORT").is_err());
+    /// ```
+    pub fn scoped<I, K, V>(vars: I) -> EnvGuard
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: AsRef<std::ffi::OsStr>,
+    {
+        let mut guard = EnvGuard {
+            saved: vec![],
+            _lock: ScopedLock::acquire(),
+        };
+        for (key, value) in vars {
+            let key = key.into();
+            guard.saved.push((key.clone(), env::var_os(&key)));
+            env::set_var(key, value);
+        }
+        guard
+    }
+
+    /// Read the .env files once, for answering many lookups
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// std::env::set_var("MYAPP_BUILDER_NAME", "foobar");
+    ///
+    /// let env = Env::builder().prefix("MYAPP_BUILDER_").build();
+    /// assert_eq!(env.var("NAME").as_deref(), Some("foobar"));
+    /// assert!(env.get_bool("VERBOSE").is_err());
+    /// ```
+    pub fn builder() -> EnvBuilder {
+        EnvBuilder {
+            files: vec![],
+            prefix: String::new(),
+            fallback: true,
+        }
+    }
+
+    /// Deserialize `T` from the variables starting with `prefix`
+    ///
+    /// This uses the default `PrefixOptions`, see `from_prefix_with`
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// #[derive(serde::Deserialize)]
+    /// struct Server { port: u16, tls: Tls }
+    /// #[derive(serde::Deserialize)]
+    /// struct Tls { enabled: bool }
+    ///
+    /// std::env::set_var("MYAPP_PORT", "8080");
+    /// std::env::set_var("MYAPP_TLS__ENABLED", "true");
+    ///
+    /// let server: Server = Env::from_prefix("MYAPP_").unwrap();
+    /// assert_eq!(server.port, 8080);
+    /// assert!(server.tls.enabled);
+    /// ```
+    pub fn from_prefix<T>(prefix: &str) -> Result<T, EnvError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Self::from_prefix_with(&PrefixOptions::new(prefix))
+    }
+
+    /// Deserialize `T` from the variables starting with the prefix in the `options`
+    ///
+    /// The prefix is removed from each variable, and the rest is split on the
+    /// separator to get the path of the field (e.g. `MYAPP_FOO__BAR` is `foo.bar`).
+    /// This uses the environment, overridden by the .env file (see `env`)
+    ///
+    /// Values are parsed for the type of their field, and lists are comma-separated
+    pub fn from_prefix_with<T>(options: &PrefixOptions) -> Result<T, EnvError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut vars = Self::vars()
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix(&*options.prefix)?;
+                let path = key
+                    .split(&*options.separator)
+                    .map(|part| match options.lowercase {
+                        true => part.to_lowercase(),
+                        false => part.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                if path.iter().any(String::is_empty) {
+                    return None;
+                }
+                Some((path, value))
+            })
+            .collect::<Vec<_>>();
+        // so conflicting variables are resolved the same way each time
+        vars.sort();
+
+        let mut root = Node::Map(vec![]);
+        for (path, value) in vars {
+            let (last, parents) = path.split_last().expect("there is always one part");
+            if let Some(map) = root.map_at(parents) {
+                match map.iter_mut().find(|(k, _)| k == last) {
+                    Some((_, node)) => *node = Node::Leaf(value),
+                    None => map.push((last.clone(), Node::Leaf(value))),
+                }
+            }
+        }
+
+        T::deserialize(root).map_err(|err| EnvError::Deserialize {
+            prefix: options.prefix.clone(),
+            error: err.to_string(),
+        })
+    }
+
+    /// The variables starting with `prefix`, with it removed, sorted by their key
+    ///
+    /// This uses the environment, overridden by the .env file (see `env`)
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// std::env::set_var("MYAPP_PLUGIN_FOO", "1");
+    /// std::env::set_var("MYAPP_PLUGIN_BAR", "2");
+    ///
+    /// let plugins = Env::iter_prefix("MYAPP_PLUGIN_").collect::<Vec<_>>();
+    /// assert_eq!(plugins, [("BAR".into(), "2".into()), ("FOO".into(), "1".into())]);
+    /// ```
+    pub fn iter_prefix(prefix: &str) -> impl Iterator<Item = (String, String)> {
+        let mut vars = Self::vars()
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(prefix)?.to_string(), value)))
+            .collect::<Vec<_>>();
+        vars.sort();
+        vars.into_iter()
+    }
+
+    /// The environment, overridden by the .env file
+    ///
+    /// Within `once`, these are only read the first time
+    pub(crate) fn vars() -> HashMap<String, String> {
+        VARS.with(|vars| match &mut *vars.borrow_mut() {
+            Some(vars) => vars.get_or_insert_with(Self::read_vars).clone(),
+            None => Self::read_vars(),
+        })
+    }
+
+    /// Runs `f` (e.g. a load, or a save), where `vars` are only read once
+    pub(crate) fn once<R>(f: impl FnOnce() -> R) -> R {
+        /// Forgets the variables, even if `f` panics
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                VARS.with(|vars| vars.replace(None));
+            }
+        }
+
+        let outer = VARS.with(|vars| {
+            let mut vars = vars.borrow_mut();
+            let outer = vars.is_some();
+            vars.get_or_insert(None);
+            outer
+        });
+        let _reset = (!outer).then(|| Reset);
+        f()
+    }
+
+    /// Reads the environment, overridden by the .env file
+    fn read_vars() -> HashMap<String, String> {
+        let mut vars = env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .collect::<HashMap<_, _>>();
+        if let Some(path) = Self::find(".env", SEARCH_DEPTH) {
+            vars.extend(Self::load(path));
+        }
+        vars
+    }
+
+    /// Find the nearest `name` file, in the current directory or its parents
+    ///
+    /// This searches up to `depth` parent directories, and stops at the root of a
+    /// repository (a directory containing `.git`)
+    pub fn find<P>(name: P, depth: usize) -> Option<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = env::current_dir().ok()?;
+        for dir in dir.ancestors().take(depth.saturating_add(1)) {
+            let path = dir.join(name.as_ref());
+            if path.is_file() {
+                return Some(path);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Read the .env file, without changing the environment
+    ///
+    /// Each line is `KEY=VALUE`, optionally prefixed with `export`. Values can be
+    /// quoted, in which case they can span multiple lines:
+    /// * `'single quotes'` are taken literally
+    /// * `"double quotes"` support the `\n`, `\r`, `\t`, `\"`, `\$` and `\\` escapes
+    ///
+    /// Otherwise the value is trimmed and ends at a ` #` comment. Unless they are
+    /// single quoted, `${VAR}` and `$VAR` in values are replaced with the variable
+    /// from an earlier line, or from the environment (`\$` is a literal `$`).
     ///
-    /// This assumes `KEY\s?=\s?"?VAL"?\s?` and turns it into {KEY:VAL}
+    /// Lines beginning with `#` are ignored. If the file cannot be read then this
+    /// is empty, use `try_load` to know why
     ///
-    /// Lines beginning with `#` are ignored
+    /// ```
+    /// # use configurable::Env;
+    /// let path = std::env::temp_dir().join("configurable-env-load.env");
+    /// std::fs::write(&path, r#"
+    /// # a comment
+    /// PLAIN = some value # and a comment
+    /// export EXPORTED=1
+    /// SINGLE='no \n escapes # here'
+    /// DOUBLE="a \"quoted\"\tvalue # here"
+    /// MULTI="first
+    /// second"
+    /// EMPTY=
+    /// GREETING=hello
+    /// EXPANDED="${GREETING}, $PLAIN \$5"
+    /// LITERAL='$GREETING'
+    /// "#).unwrap();
+    ///
+    /// let env = Env::load(&path);
+    /// assert_eq!(env["PLAIN"], "some value");
+    /// assert_eq!(env["EXPORTED"], "1");
+    /// assert_eq!(env["SINGLE"], "no \\n escapes # here");
+    /// assert_eq!(env["DOUBLE"], "a \"quoted\"\tvalue # here");
+    /// assert_eq!(env["MULTI"], "first\nsecond");
+    /// assert_eq!(env["EMPTY"], "");
+    /// assert_eq!(env["EXPANDED"], "hello, some value $5");
+    /// assert_eq!(env["LITERAL"], "$GREETING");
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
     pub fn load<P>(path: P) -> HashMap<String, String>
     where
         P: AsRef<Path>,
     {
+        Self::try_load(path).unwrap_or_default()
+    }
+
+    /// Read the .env file (see `load`), or why it cannot be read
+    pub fn try_load<P>(path: P) -> Result<HashMap<String, String>, EnvError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
         std::fs::read_to_string(path)
-            .map(|data| {
-                data.lines()
-                    .filter(|s| !s.starts_with('#'))
-                    .filter_map(|line| {
-                        let mut line = line.splitn(2, '=').map(str::trim);
-                        Some((line.next()?.into(), line.next()?.into()))
-                    })
-                    .inspect(|(k, v)| env::set_var(k, v))
-                    .collect()
+            .map(|data| parse(&data))
+            .map_err(|error| EnvError::Read {
+                path: path.to_owned(),
+                error,
             })
-            .unwrap_or_else(|_| env::vars().collect())
+    }
+
+    /// Parse the contents of a .env file (see `load`)
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// let env = Env::parse_str("USER=foo\nHOME=\"/home/$USER\"");
+    /// assert_eq!(env["HOME"], "/home/foo");
+    /// ```
+    pub fn parse_str(data: &str) -> HashMap<String, String> {
+        parse(data)
+    }
+
+    /// Read, then parse, the contents of a .env file from the `reader` (see `load`)
+    pub fn parse_reader<R>(mut reader: R) -> std::io::Result<HashMap<String, String>>
+    where
+        R: std::io::Read,
+    {
+        let mut data = String::new();
+        reader.read_to_string(&mut data)?;
+        Ok(parse(&data))
+    }
+
+    /// Read the .env files for the `profile`, without changing the environment
+    ///
+    /// These are read in this order, with later files overriding earlier ones:
+    /// * `.env`
+    /// * `.env.local`
+    /// * `.env.{profile}`
+    /// * `.env.{profile}.local`
+    ///
+    /// They are read from the directory of the nearest of them (see `find`, this is
+    /// usually where the `.env` file is). Files that cannot be read are skipped. The
+    /// values in a file can use the variables from the files before it
+    pub fn load_layered(profile: Option<&str>) -> HashMap<String, String> {
+        let mut names = vec![".env".to_string(), ".env.local".to_string()];
+        if let Some(profile) = profile {
+            names.push(format!(".env.{}", profile));
+            names.push(format!(".env.{}.local", profile));
+        }
+
+        let mut map = HashMap::new();
+        let dir = names
+            .iter()
+            .find_map(|name| Self::find(name, SEARCH_DEPTH))
+            .and_then(|path| path.parent().map(Path::to_path_buf));
+        let dir = match dir {
+            Some(dir) => dir,
+            None => return map,
+        };
+        for name in names {
+            if let Ok(data) = std::fs::read_to_string(dir.join(name)) {
+                parse_into(&data, &mut map);
+            }
+        }
+        map
+    }
+
+    /// Override the environment from the .env file
+    ///
+    /// This is `load`, but every key in the file is also set in the environment of
+    /// this process. This should be done early (e.g. at the start of `main`), as
+    /// it is not synchronized with other threads reading the environment
+    pub fn export<P>(path: P) -> HashMap<String, String>
+    where
+        P: AsRef<Path>,
+    {
+        let map = Self::load(path);
+        for (k, v) in &map {
+            env::set_var(k, v)
+        }
+        map
+    }
+
+    /// Set the variables from the nearest .env file (see `env`) in the environment
+    /// of the `command`, without changing the environment of this process
+    ///
+    /// ```no_run
+    /// # use configurable::Env;
+    /// let status = Env::apply_to(&mut std::process::Command::new("make"))
+    ///     .status()
+    ///     .unwrap();
+    /// ```
+    pub fn apply_to(command: &mut std::process::Command) -> &mut std::process::Command {
+        match Self::find(".env", SEARCH_DEPTH) {
+            Some(path) => command.envs(Self::load(path)),
+            None => command,
+        }
+    }
+
+    /// Set the `key` to `value` in the .env file, see `write`
+    pub fn set_in_file<P>(path: P, key: &str, value: &str) -> Result<(), EnvError>
+    where
+        P: AsRef<Path>,
+    {
+        let vars = std::iter::once((key.to_string(), value.to_string())).collect();
+        Self::write(path, &vars)
+    }
+
+    /// Write the `vars` to the .env file, creating it if it doesn't exist
+    ///
+    /// Keys which are already in the file are updated in place, and the rest are
+    /// appended. The other lines (and their comments) are kept as they were.
+    /// Values are quoted if they need to be. The file is replaced atomically
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// let path = std::env::temp_dir().join("configurable-env-write.env");
+    /// std::fs::write(&path, "# the token\nexport TOKEN=old\nOTHER=1\n").unwrap();
+    ///
+    /// Env::set_in_file(&path, "TOKEN", "new").unwrap();
+    /// Env::set_in_file(&path, "GREETING", "hello world").unwrap();
+    /// assert_eq!(
+    ///     std::fs::read_to_string(&path).unwrap(),
+    ///     "# the token\nexport TOKEN=new\nOTHER=1\nGREETING=\"hello world\"\n"
+    /// );
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn write<P>(path: P, vars: &HashMap<String, String>) -> Result<(), EnvError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                let path = path.to_owned();
+                return Err(EnvError::Read { path, error });
+            }
+        };
+
+        let mut out = String::with_capacity(data.len());
+        let mut written = std::collections::HashSet::new();
+        for line in lines(&data) {
+            match line.entry.filter(|entry| vars.contains_key(entry.key)) {
+                Some(entry) => {
+                    if entry.export {
+                        out.push_str("export ");
+                    }
+                    out.push_str(&format!("{}={}\n", entry.key, quote(&vars[entry.key])));
+                    written.insert(entry.key);
+                }
+                None => {
+                    for line in line.lines {
+                        out.push_str(line);
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+
+        let mut rest = vars
+            .iter()
+            .filter(|(key, _)| !written.contains(key.as_str()))
+            .collect::<Vec<_>>();
+        rest.sort();
+        for (key, value) in rest {
+            out.push_str(&format!("{}={}\n", key, quote(value)));
+        }
+
+        crate::file::replace(path, out.as_bytes(), None).map_err(|err| {
+            let error = match err {
+                crate::Error::Io { source, .. } => source,
+                err => std::io::Error::other(err.to_string()),
+            };
+            let path = path.to_owned();
+            EnvError::Write { path, error }
+        })
+    }
+}
+
+/// The thread which holds the `EnvGuard`s, and how many it holds
+static SCOPED: std::sync::Mutex<Option<(std::thread::ThreadId, usize)>> =
+    std::sync::Mutex::new(None);
+static SCOPED_RELEASED: std::sync::Condvar = std::sync::Condvar::new();
+
+/// The hold of an `EnvGuard` on the environment, which is reentrant within the thread
+///
+/// Each guard has its own, so they can be dropped in any order
+#[derive(Debug)]
+struct ScopedLock;
+
+impl ScopedLock {
+    /// Waits for the other threads to drop their guards
+    fn acquire() -> Self {
+        let current = std::thread::current().id();
+        let mut held = SCOPED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        loop {
+            match &mut *held {
+                Some((owner, count)) if *owner == current => {
+                    *count += 1;
+                    return Self;
+                }
+                Some(..) => {
+                    held = SCOPED_RELEASED
+                        .wait(held)
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)
+                }
+                None => {
+                    *held = Some((current, 1));
+                    return Self;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ScopedLock {
+    fn drop(&mut self) {
+        let mut held = SCOPED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some((_, count)) = &mut *held {
+            *count -= 1;
+            if *count == 0 {
+                *held = None;
+                SCOPED_RELEASED.notify_all();
+            }
+        }
+    }
+}
+
+/// Variables which were set in the environment, see `Env::scoped`
+///
+/// The variables are restored when this is dropped
+#[must_use = "the variables are restored when this is dropped"]
+#[derive(Debug)]
+pub struct EnvGuard {
+    saved: Vec<(String, Option<std::ffi::OsString>)>,
+    _lock: ScopedLock,
+}
+
+impl EnvGuard {
+    /// Remove the `key` from the environment too, until the guard is dropped
+    ///
+    /// ```
+    /// # use configurable::Env;
+    /// std::env::set_var("CONFIGURABLE_UNSET_HOME", "/home/foo");
+    /// let home = Env::scoped([("CONFIGURABLE_UNSET_PORT", "1234")])
+    ///     .unset("CONFIGURABLE_UNSET_HOME")
+    ///     .run(|| Env::env("CONFIGURABLE_UNSET_HOME"));
+    /// assert_eq!(home, None);
+    /// assert_eq!(std::env::var("CONFIGURABLE_UNSET_HOME").unwrap(), "/home/foo");
+    /// ```
+    pub fn unset(mut self, key: impl Into<String>) -> Self {
+        let key = key.into();
+        self.saved.push((key.clone(), env::var_os(&key)));
+        env::remove_var(key);
+        self
+    }
+
+    /// Run `f` with the variables set, then restore them
+    pub fn run<F, R>(self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        f()
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (key, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(key, value),
+                None => env::remove_var(key),
+            }
+        }
+    }
+}
+
+/// A builder for `EnvVars`, see `Env::builder`
+#[derive(Debug, Clone)]
+pub struct EnvBuilder {
+    files: Vec<PathBuf>,
+    prefix: String,
+    fallback: bool,
+}
+
+impl EnvBuilder {
+    /// Read this .env file, overriding the files before it
+    ///
+    /// If no files are given, this is the nearest .env file (see `Env::env`).
+    /// Files that cannot be read are skipped
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.files.push(path.into());
+        self
+    }
+
+    /// The prefix for every key which is looked up, e.g. `MYAPP_`
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Whether keys which aren't in the files are looked up in the environment
+    ///
+    /// This is `true` by default
+    pub fn fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Read the files
+    pub fn build(self) -> EnvVars {
+        let files = match self.files.is_empty() {
+            true => Env::find(".env", SEARCH_DEPTH).into_iter().collect(),
+            false => self.files,
+        };
+        let mut vars = HashMap::new();
+        for path in files {
+            if let Ok(data) = std::fs::read_to_string(path) {
+                parse_into(&data, &mut vars);
+            }
+        }
+        EnvVars {
+            vars,
+            prefix: self.prefix,
+            fallback: self.fallback,
+        }
+    }
+}
+
+/// Environment vars from .env files which were read once, see `Env::builder`
+///
+/// The files override the environment, like `Env::env`
+#[derive(Debug, Clone)]
+pub struct EnvVars {
+    vars: HashMap<String, String>,
+    prefix: String,
+    fallback: bool,
+}
+
+impl EnvVars {
+    /// Get the `key`, with the prefix
+    pub fn var(&self, key: &str) -> Option<String> {
+        let key = self.key(key);
+        match self.vars.get(&key) {
+            Some(value) => Some(value.clone()),
+            None if self.fallback => env::var(key).ok(),
+            None => None,
+        }
+    }
+
+    /// Get the `key`, and parse it (see `Env::get`)
+    pub fn get<T>(&self, key: &str) -> Result<T, EnvError>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        get_with(&self.key(key), self.var(key), parse_from_str)
+    }
+
+    /// Get the `key` as a `bool` (see `Env::get_bool`)
+    pub fn get_bool(&self, key: &str) -> Result<bool, EnvError> {
+        get_with(&self.key(key), self.var(key), parse_bool)
+    }
+
+    /// Get the `key` as a `Duration` (see `Env::get_duration`)
+    pub fn get_duration(&self, key: &str) -> Result<Duration, EnvError> {
+        get_with(&self.key(key), self.var(key), parse_duration)
+    }
+
+    /// Get the `key` as a path
+    pub fn get_path(&self, key: &str) -> Result<PathBuf, EnvError> {
+        get_with(&self.key(key), self.var(key), |value| Ok(value.into()))
+    }
+
+    /// Set the variables from the files in the environment of the `command`
+    ///
+    /// They are named as they are in the files, including the prefix
+    pub fn apply_to<'a>(
+        &self,
+        command: &'a mut std::process::Command,
+    ) -> &'a mut std::process::Command {
+        command.envs(&self.vars)
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+/// Options for `Env::from_prefix_with`
+///
+/// By default the separator for nested fields is `__`, and names are lowercased
+///
+/// ```
+/// # use configurable::{Env, PrefixOptions};
+/// #[derive(serde::Deserialize)]
+/// struct Opts { #[serde(rename = "logLevel")] log_level: String }
+///
+/// std::env::set_var("OTHER.logLevel", "debug");
+///
+/// let options = PrefixOptions::new("OTHER.").lowercase(false);
+/// let opts: Opts = Env::from_prefix_with(&options).unwrap();
+/// assert_eq!(opts.log_level, "debug");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefixOptions {
+    prefix: String,
+    separator: String,
+    lowercase: bool,
+}
+
+impl PrefixOptions {
+    /// Options for the variables starting with `prefix`
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            separator: "__".into(),
+            lowercase: true,
+        }
+    }
+
+    /// The separator between the names of nested fields
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Whether the names are lowercased, to match the fields
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+}
+
+/// The prefix of the variables for `application`, e.g. `FOOBAR_`
+///
+/// This is uppercased, and anything that isn't alphanumeric is a `_`
+pub(crate) fn prefix(application: &str) -> String {
+    let mut prefix = application
+        .chars()
+        .map(|ch| match ch.is_ascii_alphanumeric() {
+            true => ch.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect::<String>();
+    prefix.push('_');
+    prefix
+}
+
+/// The variable (after the prefix) of `Configurable::active_profile`
+pub(crate) const PROFILE: &str = "PROFILE";
+
+/// The variable (after the prefix) of `Configurable::read_only`
+pub(crate) const READ_ONLY: &str = "CONFIG_READONLY";
+
+/// The variables which override the fields of a `Configurable`, see `ENV_OVERRIDES`
+///
+/// These are the variable, the path of its field and its value, sorted by the variable.
+/// The variables for the profile and `read_only` aren't overrides
+pub(crate) fn overrides(application: &str) -> Vec<(String, Vec<String>, String)> {
+    let prefix = prefix(application);
+
+    let mut vars = Env::vars()
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(&*prefix)?;
+            if name == PROFILE || name == READ_ONLY {
+                return None;
+            }
+            let path = name.split("__").map(str::to_lowercase).collect::<Vec<_>>();
+            if path.iter().any(String::is_empty) {
+                return None;
+            }
+            Some((key, path, value))
+        })
+        .collect::<Vec<_>>();
+    vars.sort();
+    vars
+}
+
+/// Parses the `value` of the `key`, if it was found
+fn get_with<T>(
+    key: &str,
+    value: Option<String>,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, EnvError> {
+    let value = value.ok_or_else(|| EnvError::Missing(key.into()))?;
+    parse(value.trim()).map_err(|error| EnvError::Invalid {
+        key: key.into(),
+        value,
+        error,
+    })
+}
+
+fn parse_from_str<T>(value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|err: T::Err| err.to_string())
+}
+
+pub(crate) fn parse_bool(value: &str) -> Result<bool, String> {
+    match &*value.to_ascii_lowercase() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err("expected a boolean (e.g. `true` or `false`)".into()),
+    }
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (n, unit) = value.split_at(split);
+    let scale = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 60.0 * 60.0 * 24.0,
+        unit => return Err(format!("unknown unit `{}`", unit)),
+    };
+    n.parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .ok_or_else(|| "expected a duration (e.g. `30s` or `5m`)".into())
+}
+
+/// Parses the contents of a .env file
+fn parse(data: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    parse_into(data, &mut map);
+    map
+}
+
+/// Parses the contents of a .env file into `map`, overriding its keys
+///
+/// Variables in `map` can be used in the values
+fn parse_into(data: &str, map: &mut HashMap<String, String>) {
+    for entry in lines(data).into_iter().filter_map(|line| line.entry) {
+        let value = match entry.quote {
+            Some(quote) => unquote(&entry.value[1..], quote, map).expect("the quote is closed"),
+            None => unquoted(&entry.value, map),
+        };
+        map.insert(entry.key.to_string(), value);
+    }
+}
+
+/// A line of a .env file, with the lines after it if it has a multiline value
+struct Line<'a> {
+    lines: Vec<&'a str>,
+    entry: Option<Entry<'a>>,
+}
+
+/// The `KEY=VALUE` of a line
+struct Entry<'a> {
+    key: &'a str,
+    /// The value as it was written, with its quotes
+    value: String,
+    /// The quote around the value, if it was closed
+    quote: Option<char>,
+    export: bool,
+}
+
+/// Splits a .env file into its lines
+fn lines(data: &str) -> Vec<Line<'_>> {
+    let mut out = vec![];
+    let mut lines = data.lines();
+    while let Some(text) = lines.next() {
+        let mut line = Line {
+            lines: vec![text],
+            entry: None,
+        };
+        let text = text.trim_start();
+        if text.is_empty() || text.starts_with('#') {
+            out.push(line);
+            continue;
+        }
+        let (text, export) = match text.strip_prefix("export ") {
+            Some(text) => (text.trim_start(), true),
+            None => (text, false),
+        };
+
+        let (key, value) = match text.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim_start()),
+            None => {
+                out.push(line);
+                continue;
+            }
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            out.push(line);
+            continue;
+        }
+
+        let mut entry = Entry {
+            key,
+            value: value.to_string(),
+            quote: None,
+            export,
+        };
+        if let Some(quote @ ('"' | '\'')) = value.chars().next() {
+            // a quoted value continues until its closing quote, which can be on a later line
+            let mut ahead = lines.clone();
+            let mut quoted = value.to_string();
+            let mut more = vec![];
+            loop {
+                if closed(&quoted[1..], quote) {
+                    lines = ahead;
+                    line.lines.extend(more);
+                    entry.value = quoted;
+                    entry.quote = Some(quote);
+                    break;
+                }
+                match ahead.next() {
+                    Some(next) => {
+                        quoted.push('\n');
+                        quoted.push_str(next);
+                        more.push(next);
+                    }
+                    // it was never closed, so its just part of the value
+                    None => break,
+                }
+            }
+        }
+        line.entry = Some(entry);
+        out.push(line);
+    }
+    out
+}
+
+/// Whether `data` has a closing `quote`
+fn closed(data: &str, quote: char) -> bool {
+    let mut chars = data.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch == quote => return true,
+            '\\' if quote == '"' && chars.next().is_none() => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Formats the `value` for a .env file, quoting it if needed
+fn quote(value: &str) -> String {
+    let plain = |ch: char| ch.is_ascii_alphanumeric() || "_-./:@,+".contains(ch);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '"' | '$' | '\\' => {
+                out.push('\\');
+                out.push(ch)
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The value before its closing `quote`, if it has one
+///
+/// Variables are expanded in double quoted values
+fn unquote(data: &str, quote: char, vars: &HashMap<String, String>) -> Option<String> {
+    let mut value = String::with_capacity(data.len());
+    let mut chars = data.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch == quote => return Some(value),
+            '\\' if quote == '"' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                ch @ ('"' | '$' | '\\') => value.push(ch),
+                ch => {
+                    value.push('\\');
+                    value.push(ch)
+                }
+            },
+            '$' if quote == '"' => expand(&mut chars, vars, &mut value),
+            ch => value.push(ch),
+        }
+    }
+    None
+}
+
+/// The value up to its comment, if it has one, with its variables expanded
+fn unquoted(data: &str, vars: &HashMap<String, String>) -> String {
+    let end = data
+        .char_indices()
+        .find(|&(i, ch)| ch == '#' && data[..i].ends_with(char::is_whitespace))
+        .map_or(data.len(), |(i, _)| i);
+    let mut value = String::with_capacity(end);
+    let mut chars = data[..end].trim().chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&'$') => value.push(chars.next().unwrap()),
+            '$' => expand(&mut chars, vars, &mut value),
+            ch => value.push(ch),
+        }
+    }
+    value
+}
+
+/// Expands the variable after a `$`, either `${VAR}` or `$VAR`
+///
+/// Variables from earlier in the file take precedence over the environment, and
+/// missing variables are empty. If there is no variable name, the `$` is kept
+fn expand(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    vars: &HashMap<String, String>,
+    value: &mut String,
+) {
+    let is_name = |ch: &char| ch.is_ascii_alphanumeric() || *ch == '_';
+    let mut name = String::new();
+    if chars.peek() == Some(&'{') {
+        let mut ahead = chars.clone();
+        ahead.next();
+        loop {
+            match ahead.next() {
+                Some('}') if !name.is_empty() => break,
+                Some(ch) if is_name(&ch) => name.push(ch),
+                // without the closing brace, this isn't a variable
+                _ => {
+                    value.push('$');
+                    return;
+                }
+            }
+        }
+        *chars = ahead;
+    } else {
+        while let Some(ch) = chars.next_if(is_name) {
+            name.push(ch)
+        }
+    }
+
+    if name.is_empty() {
+        value.push('$');
+        return;
+    }
+    match vars.get(&name) {
+        Some(var) => value.push_str(var),
+        None => value.push_str(&env::var(&name).unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(data: &str) -> Vec<(String, String)> {
+        let mut vars = parse(data).into_iter().collect::<Vec<_>>();
+        vars.sort();
+        vars
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        let mut vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>();
+        vars.sort();
+        vars
+    }
+
+    #[test]
+    fn quoting() {
+        let data = r#"
+PLAIN=foo bar
+DOUBLE="foo # bar"
+SINGLE='foo # bar'
+INNER="it's"
+MULTI="first
+second"
+UNCLOSED="foo
+"#;
+        assert_eq!(
+            parsed(data),
+            vars(&[
+                ("PLAIN", "foo bar"),
+                ("DOUBLE", "foo # bar"),
+                ("SINGLE", "foo # bar"),
+                ("INNER", "it's"),
+                ("MULTI", "first\nsecond"),
+                ("UNCLOSED", "\"foo"),
+            ])
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let data = r#"
+DOUBLE="a\nb\tc\"d\\e\$f\qg"
+SINGLE='a\nb$HOME'
+PLAIN=a\$b
+"#;
+        assert_eq!(
+            parsed(data),
+            vars(&[
+                ("DOUBLE", "a\nb\tc\"d\\e$f\\qg"),
+                ("SINGLE", "a\\nb$HOME"),
+                ("PLAIN", "a$b"),
+            ])
+        );
+    }
+
+    #[test]
+    fn expansion() {
+        let data = r#"
+BASE=foo
+BRACED=${BASE}/bar
+BARE="$BASE-baz"
+MISSING=${CONFIGURABLE_TEST_MISSING}x
+DOLLAR=$ and ${
+"#;
+        assert_eq!(
+            parsed(data),
+            vars(&[
+                ("BASE", "foo"),
+                ("BRACED", "foo/bar"),
+                ("BARE", "foo-baz"),
+                ("MISSING", "x"),
+                ("DOLLAR", "$ and ${"),
+            ])
+        );
+    }
+
+    #[test]
+    fn export() {
+        let data = "export FOO=1\n  export   BAR = 2\nexported=3\n";
+        assert_eq!(
+            parsed(data),
+            vars(&[("FOO", "1"), ("BAR", "2"), ("exported", "3")])
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let data = "# FOO=1\n  # BAR=2\nBAZ=3 # a comment\nQUX=a#b\n";
+        assert_eq!(parsed(data), vars(&[("BAZ", "3"), ("QUX", "a#b")]));
+    }
+
+    #[test]
+    fn empty_values() {
+        let data = "EMPTY=\nSPACES=   \nDOUBLE=\"\"\nSINGLE=''\n";
+        assert_eq!(
+            parsed(data),
+            vars(&[
+                ("EMPTY", ""),
+                ("SPACES", ""),
+                ("DOUBLE", ""),
+                ("SINGLE", "")
+            ])
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        let data = "FOO=1\r\nBAR=\"two\r\nlines\"\r\n# comment\r\nBAZ='3'\r\n";
+        assert_eq!(
+            parsed(data),
+            vars(&[("FOO", "1"), ("BAR", "two\nlines"), ("BAZ", "3")])
+        );
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let data = "no equals\n=value\nSOME KEY=1\nexport\nGOOD=1\n";
+        assert_eq!(parsed(data), vars(&[("GOOD", "1")]));
+    }
+
+    #[test]
+    fn later_keys_override() {
+        assert_eq!(parsed("FOO=1\nFOO=2\n"), vars(&[("FOO", "2")]));
+    }
+
+    #[test]
+    fn nested_guards() {
+        let outer = Env::scoped([("CONFIGURABLE_TEST_OUTER", "1")]);
+        let inner =
+            Env::scoped([("CONFIGURABLE_TEST_INNER", "2")]).unset("CONFIGURABLE_TEST_OUTER");
+        assert!(env::var_os("CONFIGURABLE_TEST_OUTER").is_none());
+        drop(inner);
+        assert_eq!(env::var("CONFIGURABLE_TEST_OUTER").unwrap(), "1");
+        assert!(env::var_os("CONFIGURABLE_TEST_INNER").is_none());
+
+        let inner = Env::scoped([("CONFIGURABLE_TEST_INNER", "2")]);
+        drop(outer);
+        assert!(env::var_os("CONFIGURABLE_TEST_OUTER").is_none());
+        assert_eq!(env::var("CONFIGURABLE_TEST_INNER").unwrap(), "2");
+        drop(inner);
+        assert!(env::var_os("CONFIGURABLE_TEST_INNER").is_none());
+
+        // the lock was released, so another thread can take it
+        let other = std::thread::spawn(|| {
+            Env::scoped([("CONFIGURABLE_TEST_OTHER", "3")])
+                .run(|| Env::get::<u8>("CONFIGURABLE_TEST_OTHER"))
+        });
+        assert_eq!(other.join().unwrap().unwrap(), 3);
+    }
+
+    #[test]
+    fn once() {
+        let _guard = Env::scoped([("CONFIGURABLE_TEST_ONCE", "1")]);
+        let read = || Env::vars().remove("CONFIGURABLE_TEST_ONCE");
+        let (before, after) = Env::once(|| {
+            let before = read();
+            env::set_var("CONFIGURABLE_TEST_ONCE", "2");
+            (before, Env::once(read))
+        });
+        assert_eq!(
+            (before.as_deref(), after.as_deref()),
+            (Some("1"), Some("1"))
+        );
+        assert_eq!(read().as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        for value in [
+            "plain",
+            "",
+            "a b",
+            "a\"b",
+            "$HOME",
+            "a\\b",
+            "a\nb\r\tc",
+            "it's",
+        ] {
+            let data = format!("KEY={}\n", quote(value));
+            assert_eq!(parsed(&data), vars(&[("KEY", value)]), "{}", data);
+        }
     }
 }
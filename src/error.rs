@@ -1,14 +1,468 @@
+use std::path::{Path, PathBuf};
+
+/// A `Result` whose error defaults to `Error`
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
 /// Errors produced by these traits
+///
+/// More variants can be added, use `kind` to handle the broad categories
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    /// Cannot write
+    /// Cannot write to a writer (e.g. in `Configurable::save_to`)
     Write(std::io::Error),
-    /// Cannt read
+    /// Cannot read from a reader (e.g. in `Configurable::load_from`)
     Read(std::io::Error),
+    /// There is no home directory, so there is nowhere to store the files
+    ///
+    /// An application can fall back to a directory of its own (e.g. next to the
+    /// executable) by implementing `Configurable::try_dir`
+    NoHomeDirectory,
+    /// The file doesn't exist (or doesn't contain the `Configurable::SECTION`)
+    NotFound(PathBuf),
+    /// Cannot do the `op` to a file, or a directory
+    Io {
+        /// What was being done
+        op: Op,
+        /// The file, or directory
+        path: PathBuf,
+        /// Why it failed
+        source: std::io::Error,
+    },
+    /// The file cannot be parsed
+    Parse {
+        /// The file
+        path: PathBuf,
+        /// Why it cannot be parsed (e.g. `Error::TomlRead`)
+        source: Box<Error>,
+        /// The contents of the file, to show where the error is
+        ///
+        /// This is only kept with the `miette` feature, when the error has a position
+        contents: Option<String>,
+    },
+    /// A field cannot be deserialized
+    ///
+    /// ```
+    /// # #[cfg(feature = "json")] {
+    /// # use configurable::{Error, ErrorKind, Format};
+    /// #[derive(Debug, serde::Deserialize)]
+    /// struct Server {
+    ///     port: u16,
+    /// }
+    ///
+    /// #[derive(Debug, serde::Deserialize)]
+    /// struct Config {
+    ///     server: Server,
+    /// }
+    ///
+    /// let data = br#"{ "server": { "port": "eighty" } }"#;
+    /// let err = configurable::JsonFormat::deserialize::<Config>(data).unwrap_err();
+    /// match &err {
+    ///     Error::Field { field, .. } => assert_eq!(field, "server.port"),
+    ///     err => panic!("{}", err),
+    /// }
+    /// assert_eq!(err.kind(), ErrorKind::Parse);
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "json read error: invalid type: string \"eighty\", expected u16 \
+    ///      at line 1 column 30 in field `server.port`"
+    /// );
+    /// # }
+    /// ```
+    Field {
+        /// The path of the field (e.g. `server.port`)
+        field: String,
+        /// Why it cannot be deserialized
+        source: Box<Error>,
+    },
     /// Deserialization error
     TomlRead(toml::de::Error),
     /// Serialization error
     TomlWrite(toml::ser::Error),
+    /// JSON deserialization error
+    #[cfg(feature = "json")]
+    JsonRead(serde_json::Error),
+    /// JSON serialization error
+    #[cfg(feature = "json")]
+    JsonWrite(serde_json::Error),
+    /// JSON5 deserialization error
+    #[cfg(feature = "json5")]
+    Json5Read(json5::Error),
+    /// JSON5 serialization error
+    #[cfg(feature = "json5")]
+    Json5Write(json5::Error),
+    /// YAML deserialization error
+    #[cfg(feature = "yaml")]
+    YamlRead(serde_yaml::Error),
+    /// YAML serialization error
+    #[cfg(feature = "yaml")]
+    YamlWrite(serde_yaml::Error),
+    /// RON deserialization error
+    #[cfg(feature = "ron")]
+    RonRead(ron::error::SpannedError),
+    /// RON serialization error
+    #[cfg(feature = "ron")]
+    RonWrite(ron::Error),
+    /// Bincode deserialization error
+    #[cfg(feature = "bincode")]
+    BincodeRead(bincode::Error),
+    /// Bincode serialization error
+    #[cfg(feature = "bincode")]
+    BincodeWrite(bincode::Error),
+    /// MessagePack deserialization error
+    #[cfg(feature = "msgpack")]
+    MsgPackRead(rmp_serde::decode::Error),
+    /// MessagePack serialization error
+    #[cfg(feature = "msgpack")]
+    MsgPackWrite(rmp_serde::encode::Error),
+    /// CBOR deserialization error
+    #[cfg(feature = "cbor")]
+    CborRead(ciborium::de::Error<std::io::Error>),
+    /// CBOR serialization error
+    #[cfg(feature = "cbor")]
+    CborWrite(ciborium::ser::Error<std::io::Error>),
+    /// INI deserialization error
+    #[cfg(feature = "ini")]
+    IniRead(String),
+    /// INI serialization error
+    #[cfg(feature = "ini")]
+    IniWrite(String),
+    /// KDL deserialization error
+    #[cfg(feature = "kdl")]
+    KdlRead(String),
+    /// KDL serialization error
+    #[cfg(feature = "kdl")]
+    KdlWrite(String),
+    /// XML deserialization error
+    #[cfg(feature = "xml")]
+    XmlRead(quick_xml::DeError),
+    /// XML serialization error
+    #[cfg(feature = "xml")]
+    XmlWrite(quick_xml::SeError),
+    /// Cannot parse the existing document for a preserving save
+    #[cfg(feature = "preserve")]
+    TomlEdit(toml_edit::TomlError),
+    /// The file doesn't match its checksum
+    #[cfg(feature = "checksum")]
+    Corrupt(PathBuf),
+    /// The file could not be moved to the trash
+    #[cfg(feature = "trash")]
+    Trash(trash::Error),
+    /// The blocking task of an `AsyncConfigurable` method was cancelled (e.g. the
+    /// runtime is shutting down)
+    #[cfg(feature = "tokio")]
+    Cancelled,
+    /// A `figment::Provider` cannot be used, see `Layered::provider`
+    #[cfg(feature = "figment")]
+    Figment(Box<figment::Error>),
+    /// The file is larger than `Configurable::MAX_SIZE`
+    TooLarge {
+        /// The file
+        path: PathBuf,
+        /// The size of the file, in bytes
+        size: u64,
+        /// The largest size allowed, in bytes
+        max: u64,
+    },
+    /// No `Format` is known for the extension of this file name
+    UnknownFormat(String),
+    /// Error produced by a custom `Format`
+    Format(Box<dyn std::error::Error + Send + Sync>),
+    /// An environment variable cannot be used, see `Configurable::ENV_OVERRIDES`
+    Env(EnvError),
+    /// An override cannot be applied, see `Configurable::apply_overrides` and `Document::set`
+    Override {
+        /// The override (e.g. `server.port=8080`)
+        assignment: String,
+        /// Why it cannot be applied
+        error: String,
+    },
+    /// The files extend or include each other, see `Configurable::EXTENDS` and
+    /// `Configurable::INCLUDE`
+    ///
+    /// This is the files, from the one which was loaded to the one which was
+    /// extended again
+    Cycle(Vec<PathBuf>),
+    /// The file cannot be written, because the configuration is read-only
+    ///
+    /// See `Configurable::READ_ONLY`
+    ReadOnly(PathBuf),
+    /// The name of an instance (or the id of an entry) cannot be used in a file name,
+    /// or the `NAME` of the file (or the `ORGANIZATION` or `APPLICATION`) is empty
+    ///
+    /// See `Configurable::load_named` and `Collection`
+    InvalidName(String),
+    /// Several sources cannot be loaded (e.g. the file and its secrets)
+    ///
+    /// See `Error::errors`
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// The category of the error
+    ///
+    /// ```
+    /// # use configurable::{Error, ErrorKind};
+    /// let err = Error::NotFound("config.toml".into());
+    /// assert_eq!(err.kind(), ErrorKind::NotFound);
+    /// ```
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Write(..) | Error::Read(..) | Error::Io { .. } => ErrorKind::Io,
+            #[cfg(feature = "trash")]
+            Error::Trash(..) => ErrorKind::Io,
+            #[cfg(feature = "figment")]
+            Error::Figment(..) => ErrorKind::Parse,
+            #[cfg(feature = "tokio")]
+            Error::Cancelled => ErrorKind::Cancelled,
+            Error::NoHomeDirectory => ErrorKind::NoHomeDirectory,
+            Error::NotFound(..) => ErrorKind::NotFound,
+            Error::Parse { .. } | Error::TomlRead(..) => ErrorKind::Parse,
+            Error::Field { source, .. } => source.kind(),
+            #[cfg(feature = "json")]
+            Error::JsonRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "json5")]
+            Error::Json5Read(..) => ErrorKind::Parse,
+            #[cfg(feature = "yaml")]
+            Error::YamlRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "ron")]
+            Error::RonRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "bincode")]
+            Error::BincodeRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "cbor")]
+            Error::CborRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "ini")]
+            Error::IniRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "kdl")]
+            Error::KdlRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "xml")]
+            Error::XmlRead(..) => ErrorKind::Parse,
+            #[cfg(feature = "preserve")]
+            Error::TomlEdit(..) => ErrorKind::Parse,
+            Error::TomlWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "json")]
+            Error::JsonWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "json5")]
+            Error::Json5Write(..) => ErrorKind::Serialize,
+            #[cfg(feature = "yaml")]
+            Error::YamlWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "ron")]
+            Error::RonWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "bincode")]
+            Error::BincodeWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "cbor")]
+            Error::CborWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "ini")]
+            Error::IniWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "kdl")]
+            Error::KdlWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "xml")]
+            Error::XmlWrite(..) => ErrorKind::Serialize,
+            #[cfg(feature = "checksum")]
+            Error::Corrupt(..) => ErrorKind::Corrupt,
+            Error::TooLarge { .. } => ErrorKind::TooLarge,
+            Error::UnknownFormat(..) => ErrorKind::UnknownFormat,
+            Error::Format(..) => ErrorKind::Format,
+            Error::Env(..) => ErrorKind::Env,
+            Error::Override { .. } => ErrorKind::Override,
+            Error::Cycle(..) => ErrorKind::Parse,
+            Error::ReadOnly(..) => ErrorKind::ReadOnly,
+            Error::InvalidName(..) => ErrorKind::InvalidName,
+            Error::Multiple(..) => ErrorKind::Multiple,
+        }
+    }
+
+    /// The errors this is made of
+    ///
+    /// This is every error of an `Error::Multiple`, otherwise just this one
+    ///
+    /// ```
+    /// # use configurable::Error;
+    /// let err = Error::NotFound("config.toml".into());
+    /// assert_eq!(err.errors().len(), 1);
+    ///
+    /// let err = Error::Multiple(vec![
+    ///     Error::NotFound("config.toml".into()),
+    ///     Error::NotFound("secrets.toml".into()),
+    /// ]);
+    /// assert_eq!(err.errors().len(), 2);
+    /// ```
+    pub fn errors(&self) -> &[Error] {
+        match self {
+            Error::Multiple(errors) => errors,
+            err => std::slice::from_ref(err),
+        }
+    }
+
+    /// Combines the errors, returning `None` if there are none
+    ///
+    /// A single error is kept as it is, and nested `Error::Multiple`s are flattened
+    pub(crate) fn multiple(errors: impl IntoIterator<Item = Error>) -> Option<Self> {
+        let mut errors = errors.into_iter();
+        errors.next().map(|first| Self::combine(first, errors))
+    }
+
+    /// Combines `first` with the `rest` of the errors
+    ///
+    /// A single error is kept as it is, and nested `Error::Multiple`s are flattened
+    pub(crate) fn combine(first: Error, rest: impl IntoIterator<Item = Error>) -> Self {
+        let mut errors = std::iter::once(first)
+            .chain(rest)
+            .flat_map(|err| match err {
+                Error::Multiple(errors) => errors,
+                err => vec![err],
+            })
+            .collect::<Vec<_>>();
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => Error::Multiple(errors),
+        }
+    }
+
+    /// Wraps an io error from doing the `op` to the file at `path`
+    ///
+    /// A file which cannot be read because it doesn't exist is an `Error::NotFound`
+    pub(crate) fn io(op: Op, path: &Path) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| match (op, source.kind()) {
+            (Op::Read, std::io::ErrorKind::NotFound) => Error::NotFound(path.to_owned()),
+            _ => Error::Io {
+                op,
+                path: path.to_owned(),
+                source,
+            },
+        }
+    }
+
+    /// Adds the file at `path` to an error from reading it
+    ///
+    /// Errors which already have a path are kept as they are
+    pub(crate) fn in_file(path: &Path) -> impl FnOnce(Self) -> Self + '_ {
+        move |err| match err {
+            Error::Read(source) => Error::io(Op::Read, path)(source),
+            err @ Error::NotFound(..)
+            | err @ Error::Io { .. }
+            | err @ Error::Parse { .. }
+            | err @ Error::TooLarge { .. }
+            | err @ Error::Env(..)
+            | err @ Error::Cycle(..)
+            | err @ Error::ReadOnly(..)
+            | err @ Error::Multiple(..) => err,
+            #[cfg(feature = "checksum")]
+            err @ Error::Corrupt(..) => err,
+            err => {
+                #[cfg(feature = "miette")]
+                let contents = crate::diagnostic::contents(&err, path);
+                #[cfg(not(feature = "miette"))]
+                let contents = None;
+                Error::Parse {
+                    path: path.to_owned(),
+                    source: Box::new(err),
+                    contents,
+                }
+            }
+        }
+    }
+}
+
+/// An io error from a reader is an `Error::Read`
+///
+/// Errors from a file should use `Error::Io`, which has its path
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Read(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::TomlRead(err)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Error::TomlWrite(err)
+    }
+}
+
+impl From<EnvError> for Error {
+    fn from(err: EnvError) -> Self {
+        Error::Env(err)
+    }
+}
+
+/// The category of an `Error`, see `Error::kind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// There is no home directory
+    NoHomeDirectory,
+    /// The file doesn't exist
+    NotFound,
+    /// A file cannot be read or written, or a reader or writer failed
+    Io,
+    /// A file cannot be parsed
+    Parse,
+    /// A value cannot be serialized
+    Serialize,
+    /// The file doesn't match its checksum
+    Corrupt,
+    /// The file is too large
+    TooLarge,
+    /// No `Format` is known for a file
+    UnknownFormat,
+    /// A custom `Format` failed
+    Format,
+    /// An environment variable cannot be used
+    Env,
+    /// An override cannot be applied
+    Override,
+    /// The configuration is read-only
+    ReadOnly,
+    /// The name of an instance is invalid
+    InvalidName,
+    /// An async task was cancelled
+    Cancelled,
+    /// Several errors occurred, see `Error::errors`
+    Multiple,
+}
+
+/// What was being done to a file, see `Error::Io`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Reading it (or its metadata)
+    Read,
+    /// Writing it (or its metadata)
+    Write,
+    /// Creating the directory
+    CreateDir,
+    /// Locking it, see `FileLock`
+    Lock,
+    /// Renaming it
+    Rename,
+    /// Copying it
+    Copy,
+    /// Removing it
+    Remove,
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            Op::Read => "read",
+            Op::Write => "write",
+            Op::CreateDir => "create the directory",
+            Op::Lock => "lock",
+            Op::Rename => "rename",
+            Op::Copy => "copy",
+            Op::Remove => "remove",
+        };
+        f.write_str(op)
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -16,8 +470,97 @@ impl std::fmt::Display for Error {
         match self {
             Error::Write(err) => write!(f, "cannot write: {}", err),
             Error::Read(err) => write!(f, "cannot read: {}", err),
+            Error::NoHomeDirectory => write!(f, "no home directory was found"),
+            Error::NotFound(path) => write!(f, "{} was not found", path.display()),
+            Error::Io { op, path, source } => {
+                write!(f, "cannot {} {}: {}", op, path.display(), source)
+            }
+            Error::Parse { path, source, .. } => {
+                write!(f, "cannot parse {}: {}", path.display(), source)
+            }
+            Error::Field { field, source } => write!(f, "{} in field `{}`", source, field),
             Error::TomlRead(err) => write!(f, "toml read error: {}", err),
             Error::TomlWrite(err) => write!(f, "toml write error: {}", err),
+            #[cfg(feature = "json")]
+            Error::JsonRead(err) => write!(f, "json read error: {}", err),
+            #[cfg(feature = "json")]
+            Error::JsonWrite(err) => write!(f, "json write error: {}", err),
+            #[cfg(feature = "json5")]
+            Error::Json5Read(err) => write!(f, "json5 read error: {}", err),
+            #[cfg(feature = "json5")]
+            Error::Json5Write(err) => write!(f, "json5 write error: {}", err),
+            #[cfg(feature = "yaml")]
+            Error::YamlRead(err) => write!(f, "yaml read error: {}", err),
+            #[cfg(feature = "yaml")]
+            Error::YamlWrite(err) => write!(f, "yaml write error: {}", err),
+            #[cfg(feature = "ron")]
+            Error::RonRead(err) => write!(f, "ron read error: {}", err),
+            #[cfg(feature = "ron")]
+            Error::RonWrite(err) => write!(f, "ron write error: {}", err),
+            #[cfg(feature = "bincode")]
+            Error::BincodeRead(err) => write!(f, "bincode read error: {}", err),
+            #[cfg(feature = "bincode")]
+            Error::BincodeWrite(err) => write!(f, "bincode write error: {}", err),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackRead(err) => write!(f, "msgpack read error: {}", err),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackWrite(err) => write!(f, "msgpack write error: {}", err),
+            #[cfg(feature = "cbor")]
+            Error::CborRead(err) => write!(f, "cbor read error: {}", err),
+            #[cfg(feature = "cbor")]
+            Error::CborWrite(err) => write!(f, "cbor write error: {}", err),
+            #[cfg(feature = "ini")]
+            Error::IniRead(err) => write!(f, "ini read error: {}", err),
+            #[cfg(feature = "ini")]
+            Error::IniWrite(err) => write!(f, "ini write error: {}", err),
+            #[cfg(feature = "kdl")]
+            Error::KdlRead(err) => write!(f, "kdl read error: {}", err),
+            #[cfg(feature = "kdl")]
+            Error::KdlWrite(err) => write!(f, "kdl write error: {}", err),
+            #[cfg(feature = "xml")]
+            Error::XmlRead(err) => write!(f, "xml read error: {}", err),
+            #[cfg(feature = "xml")]
+            Error::XmlWrite(err) => write!(f, "xml write error: {}", err),
+            #[cfg(feature = "preserve")]
+            Error::TomlEdit(err) => write!(f, "toml edit error: {}", err),
+            #[cfg(feature = "checksum")]
+            Error::Corrupt(path) => write!(f, "checksum mismatch for: {}", path.display()),
+            #[cfg(feature = "trash")]
+            Error::Trash(err) => write!(f, "cannot move to the trash: {}", err),
+            #[cfg(feature = "tokio")]
+            Error::Cancelled => write!(f, "the task was cancelled"),
+            #[cfg(feature = "figment")]
+            Error::Figment(err) => write!(f, "figment error: {}", err),
+            Error::TooLarge { path, size, max } => write!(
+                f,
+                "{} is {} bytes, which is larger than the limit of {} bytes",
+                path.display(),
+                size,
+                max
+            ),
+            Error::UnknownFormat(name) => write!(f, "unknown format for: {}", name),
+            Error::Format(err) => write!(f, "format error: {}", err),
+            Error::Env(err) => write!(f, "env error: {}", err),
+            Error::Override { assignment, error } => {
+                write!(f, "cannot apply `{}`: {}", assignment, error)
+            }
+            Error::Cycle(paths) => {
+                let paths = paths.iter().map(|path| path.display().to_string());
+                let paths = paths.collect::<Vec<_>>().join(" -> ");
+                write!(f, "the files extend or include each other: {}", paths)
+            }
+            Error::ReadOnly(path) => {
+                write!(
+                    f,
+                    "cannot write {}, the configuration is read-only",
+                    path.display()
+                )
+            }
+            Error::InvalidName(name) => write!(f, "`{}` cannot be used as a file name", name),
+            Error::Multiple(errors) => {
+                write!(f, "{} errors occurred", errors.len())?;
+                errors.iter().try_for_each(|err| write!(f, "\n- {}", err))
+            }
         }
     }
 }
@@ -25,9 +568,140 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::Write(err) | Error::Read(err) => Some(err as &(dyn std::error::Error)),
-            Error::TomlRead(err) => Some(err as &(dyn std::error::Error)),
-            Error::TomlWrite(err) => Some(err as &(dyn std::error::Error)),
+            Error::Write(err) | Error::Read(err) => Some(err as &dyn std::error::Error),
+            Error::Io { source, .. } => Some(source as &dyn std::error::Error),
+            Error::Parse { source, .. } | Error::Field { source, .. } => {
+                Some(&**source as &dyn std::error::Error)
+            }
+            Error::TomlRead(err) => Some(err as &dyn std::error::Error),
+            Error::TomlWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "json")]
+            Error::JsonRead(err) | Error::JsonWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "json5")]
+            Error::Json5Read(err) | Error::Json5Write(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "yaml")]
+            Error::YamlRead(err) | Error::YamlWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "ron")]
+            Error::RonRead(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "ron")]
+            Error::RonWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "bincode")]
+            Error::BincodeRead(err) | Error::BincodeWrite(err) => {
+                Some(&**err as &dyn std::error::Error)
+            }
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackRead(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "cbor")]
+            Error::CborRead(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "cbor")]
+            Error::CborWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "preserve")]
+            Error::TomlEdit(err) => Some(err as &dyn std::error::Error),
+            Error::Format(err) => Some(&**err as &dyn std::error::Error),
+            Error::Env(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "ini")]
+            Error::IniRead(..) | Error::IniWrite(..) => None,
+            #[cfg(feature = "kdl")]
+            Error::KdlRead(..) | Error::KdlWrite(..) => None,
+            #[cfg(feature = "xml")]
+            Error::XmlRead(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "xml")]
+            Error::XmlWrite(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "checksum")]
+            Error::Corrupt(..) => None,
+            #[cfg(feature = "trash")]
+            Error::Trash(err) => Some(err as &dyn std::error::Error),
+            #[cfg(feature = "tokio")]
+            Error::Cancelled => None,
+            #[cfg(feature = "figment")]
+            Error::Figment(err) => Some(&**err as &dyn std::error::Error),
+            Error::NoHomeDirectory | Error::NotFound(..) => None,
+            Error::TooLarge { .. } => None,
+            Error::UnknownFormat(..) => None,
+            Error::Override { .. } | Error::Cycle(..) | Error::Multiple(..) => None,
+            Error::ReadOnly(..) | Error::InvalidName(..) => None,
+        }
+    }
+}
+
+/// Errors produced by `Env`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EnvError {
+    /// The variable isn't set
+    Missing(String),
+    /// The .env file cannot be read
+    Read {
+        /// The .env file
+        path: PathBuf,
+        /// Why it cannot be read
+        error: std::io::Error,
+    },
+    /// The .env file cannot be written
+    Write {
+        /// The .env file
+        path: PathBuf,
+        /// Why it cannot be written
+        error: std::io::Error,
+    },
+    /// These required variables aren't set, see `Env::require`
+    Required(Vec<String>),
+    /// The variable cannot be parsed
+    Invalid {
+        /// The variable
+        key: String,
+        /// Its value
+        value: String,
+        /// Why it cannot be parsed
+        error: String,
+    },
+    /// The variables with the prefix cannot be deserialized
+    Deserialize {
+        /// The prefix of the variables
+        prefix: String,
+        /// Why they cannot be deserialized
+        error: String,
+    },
+}
+
+impl std::fmt::Display for EnvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvError::Missing(key) => write!(f, "environment variable `{}` is not set", key),
+            EnvError::Read { path, error } => {
+                write!(f, "cannot read {}: {}", path.display(), error)
+            }
+            EnvError::Write { path, error } => {
+                write!(f, "cannot write {}: {}", path.display(), error)
+            }
+            EnvError::Required(keys) => {
+                let keys = keys.iter().map(|key| format!("`{}`", key));
+                let keys = keys.collect::<Vec<_>>().join(", ");
+                write!(f, "required environment variables are not set: {}", keys)
+            }
+            EnvError::Invalid { key, value, error } => write!(
+                f,
+                "environment variable `{}` has an invalid value ({:?}): {}",
+                key, value, error
+            ),
+            EnvError::Deserialize { prefix, error } => write!(
+                f,
+                "cannot deserialize the environment variables with the prefix `{}`: {}",
+                prefix, error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EnvError::Read { error, .. } | EnvError::Write { error, .. } => {
+                Some(error as &dyn std::error::Error)
+            }
+            _ => None,
         }
     }
 }
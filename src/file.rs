@@ -0,0 +1,460 @@
+use super::*;
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write as _};
+use std::path::{Path, PathBuf};
+
+/// Opens the file at `path` for `T`
+///
+/// This checks the size of the file against `T::MAX_SIZE`
+pub(crate) fn open<T: Configurable>(path: &Path) -> Result<BufReader<File>, Error> {
+    LoadToken::read(path);
+    let file = File::open(path).map_err(Error::io(Op::Read, path))?;
+    if let Some(max) = T::MAX_SIZE {
+        let size = file.metadata().map_err(Error::io(Op::Read, path))?.len();
+        if size > max {
+            let path = path.to_owned();
+            return Err(Error::TooLarge { path, size, max });
+        }
+    }
+    Ok(BufReader::new(file))
+}
+
+/// Reads the file at `path` for `T`
+///
+/// This verifies the data against its checksum, if `T::CHECKSUM` is set
+pub(crate) fn read<T: Configurable>(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    open::<T>(path)?
+        .read_to_end(&mut data)
+        .map_err(Error::io(Op::Read, path))?;
+    #[cfg(feature = "checksum")]
+    {
+        if T::CHECKSUM {
+            verify(path, &data)?;
+        }
+    }
+    Ok(data)
+}
+
+/// Whether the files for `T` have a checksum
+#[cfg_attr(not(feature = "checksum"), allow(clippy::extra_unused_type_parameters))]
+pub(crate) fn checksummed<T: Configurable>() -> bool {
+    #[cfg(feature = "checksum")]
+    {
+        T::CHECKSUM
+    }
+    #[cfg(not(feature = "checksum"))]
+    {
+        false
+    }
+}
+
+/// Writes the data to the file at `path` for `T`
+///
+/// Nothing is written if the file already has this data (only its mode is updated),
+/// otherwise this rotates `T::BACKUPS` copies of the existing file first. The file
+/// is replaced atomically, so it is never partially written
+pub(crate) fn write<T: Configurable>(path: &Path, data: &[u8]) -> Result<(), Error> {
+    writable::<T>(path)?;
+    #[cfg(not(unix))]
+    if T::FILE_MODE.is_some() {
+        return Err(unsupported_mode(path));
+    }
+    if !unchanged(path, data) {
+        if T::BACKUPS > 0 && path.exists() {
+            rotate(path, T::BACKUPS)?;
+        }
+        replace(path, data, T::FILE_MODE)?;
+    } else {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            if let Some(mode) = T::FILE_MODE {
+                let perms = fs::Permissions::from_mode(mode);
+                fs::set_permissions(path, perms).map_err(Error::io(Op::Write, path))?;
+            }
+        }
+    }
+    #[cfg(feature = "checksum")]
+    {
+        if T::CHECKSUM {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let sum = format!("{}  {}\n", digest(data), name);
+            let path = sidecar(path, "sha256");
+            if !unchanged(&path, sum.as_bytes()) {
+                replace(&path, sum.as_bytes(), T::FILE_MODE)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An advisory lock on a configuration file, it is released when dropped
+///
+/// This is held across processes (e.g. with `flock` on Unix). Within a thread,
+/// taking the lock again is a no-op, so a `Configurable` can be loaded and saved
+/// while it is held. A shared lock (e.g. while loading) can't be taken again as an
+/// exclusive one, that is an `Error::Io` rather than a deadlock. A lock from
+/// `AsyncConfigurable::lock_async` can be taken again by any thread of the
+/// process, as the async methods run on other threads
+///
+/// See `Configurable::lock`
+#[derive(Debug)]
+pub struct FileLock {
+    held: Option<(usize, PathBuf)>,
+}
+
+impl FileLock {
+    /// Takes the lock at `path` again, if it is already held by this thread
+    ///
+    /// An exclusive lock can't be taken again if only a shared lock is held
+    fn reenter(path: &Path, shared: bool) -> Option<Result<Self, Error>> {
+        let current = std::thread::current().id();
+        let mut held = held();
+        let entry = held
+            .iter_mut()
+            .find(|held| held.path == path && held.owner.is_none_or(|owner| owner == current))?;
+        if entry.shared && !shared {
+            let msg = "only a shared lock is held, it cannot be taken as an exclusive lock";
+            let source = std::io::Error::new(std::io::ErrorKind::WouldBlock, msg);
+            return Some(Err(Error::io(Op::Lock, path)(source)));
+        }
+        entry.count += 1;
+        Some(Ok(Self {
+            held: Some((entry.id, entry.path.clone())),
+        }))
+    }
+
+    /// Records that `file` holds the lock at `path` (a shared lock, if `shared` is
+    /// set), for this thread
+    fn hold(path: PathBuf, file: File, shared: bool) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        held().push(Held {
+            id,
+            path: path.clone(),
+            owner: Some(std::thread::current().id()),
+            shared,
+            count: 1,
+            _file: file,
+        });
+        Self {
+            held: Some((id, path)),
+        }
+    }
+
+    /// Lets any thread of the process take the lock again
+    #[cfg(feature = "tokio")]
+    pub(crate) fn for_process(self) -> Self {
+        if let Some((id, _)) = &self.held {
+            if let Some(entry) = held().iter_mut().find(|held| held.id == *id) {
+                entry.owner = None;
+            }
+        }
+        self
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let id = match self.held.take() {
+            Some((id, _)) => id,
+            None => return,
+        };
+        let mut held = held();
+        if let Some(pos) = held.iter().position(|held| held.id == id) {
+            held[pos].count -= 1;
+            if held[pos].count == 0 {
+                // this closes the file, which releases the lock
+                held.swap_remove(pos);
+            }
+        }
+    }
+}
+
+/// A lock held by this process
+///
+/// These are shared by every `FileLock` for it, so they can be dropped in any order
+/// (and on any thread)
+#[derive(Debug)]
+struct Held {
+    id: usize,
+    path: PathBuf,
+    /// The thread which can take the lock again, or `None` for any thread
+    owner: Option<std::thread::ThreadId>,
+    /// Whether this is a shared lock, rather than an exclusive one
+    shared: bool,
+    count: usize,
+    _file: File,
+}
+
+/// The locks held by this process
+fn held() -> std::sync::MutexGuard<'static, Vec<Held>> {
+    static HELD: std::sync::Mutex<Vec<Held>> = std::sync::Mutex::new(Vec::new());
+    HELD.lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Exclusively locks the file at `path`, by locking `path.lock`
+///
+/// This blocks until the lock is acquired
+pub(crate) fn lock(path: &Path) -> Result<FileLock, Error> {
+    let path = sidecar(path, "lock");
+    if let Some(lock) = FileLock::reenter(&path, false) {
+        return lock;
+    }
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(Error::io(Op::Lock, &path))?;
+    file.lock().map_err(Error::io(Op::Lock, &path))?;
+    Ok(FileLock::hold(path, file, false))
+}
+
+/// Takes a shared lock on the file at `path`, by locking `path.lock`
+///
+/// This blocks until the lock is acquired. The lock file isn't created, if it
+/// doesn't exist (or cannot be opened) then nothing is locked
+pub(crate) fn lock_shared(path: &Path) -> Result<FileLock, Error> {
+    let path = sidecar(path, "lock");
+    if let Some(lock) = FileLock::reenter(&path, true) {
+        return lock;
+    }
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(..) => return Ok(FileLock { held: None }),
+    };
+    file.lock_shared().map_err(Error::io(Op::Lock, &path))?;
+    Ok(FileLock::hold(path, file, true))
+}
+
+/// Writes the data to a temporary file next to `path`, then renames it over `path`
+///
+/// Old temporary files are from writes that were abandoned (e.g. the process was
+/// killed), and are removed.
+/// The file is created with `mode` (see `Configurable::FILE_MODE`), or else with the
+/// permissions of the existing file. A symlink is followed, so its target is replaced
+///
+/// A `mode` is an `Error::Io` on platforms other than Unix
+pub(crate) fn replace(path: &Path, data: &[u8], mode: Option<u32>) -> Result<(), Error> {
+    #[cfg(not(unix))]
+    if mode.is_some() {
+        return Err(unsupported_mode(path));
+    }
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    // a missing file has nothing to resolve, so it is created at `path`
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    remove_temp_files(path);
+
+    let existing = fs::metadata(path).ok().map(|meta| meta.permissions());
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    let temp = sidecar(path, &format!("{}.{}.tmp", std::process::id(), n));
+    let res = create(&temp, mode).and_then(|mut file| {
+        if let (None, Some(perms)) = (mode, existing) {
+            file.set_permissions(perms)
+                .map_err(Error::io(Op::Write, &temp))?;
+        }
+        file.write_all(data).map_err(Error::io(Op::Write, &temp))?;
+        file.sync_all().map_err(Error::io(Op::Write, &temp))
+    });
+    let res = res.and_then(|_| fs::rename(&temp, path).map_err(Error::io(Op::Rename, path)));
+    if let Err(err) = res {
+        let _ = fs::remove_file(temp);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// The error for a file mode on a platform which doesn't have them
+#[cfg(not(unix))]
+fn unsupported_mode(path: &Path) -> Error {
+    let source = std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "a file mode can only be set on unix",
+    );
+    Error::io(Op::Write, path)(source)
+}
+
+/// Removes the old temporary files of `path` (e.g. `config.toml.123.0.tmp`)
+///
+/// Only the ones which weren't modified for an hour are removed, so the files of
+/// other writers aren't. This is best-effort, as they are only left around if a
+/// write was abandoned
+fn remove_temp_files(path: &Path) {
+    const ABANDONED: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+    let (dir, name) = match (path.parent(), path.file_name().and_then(|s| s.to_str())) {
+        (Some(dir), Some(name)) => (dir, name),
+        _ => return,
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(..) => return,
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let is_temp = file_name
+            .to_str()
+            .and_then(|s| s.strip_prefix(name))
+            .and_then(|s| s.strip_prefix('.'))
+            .and_then(|s| s.strip_suffix(".tmp"))
+            .and_then(|s| s.split_once('.'))
+            .filter(|(pid, n)| {
+                let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+                digits(pid) && digits(n)
+            })
+            .is_some();
+        let abandoned = || {
+            let modified = entry.metadata().and_then(|meta| meta.modified());
+            modified
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age >= ABANDONED)
+        };
+        if is_temp && abandoned() {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Creates, or truncates, the file at `path`
+///
+/// On Unix this sets the mode of the file to `mode`, before anything is written
+fn create(path: &Path, mode: Option<u32>) -> Result<File, Error> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+        if let Some(mode) = mode {
+            // this only applies to new files
+            options.mode(mode);
+            let file = options.open(path).map_err(Error::io(Op::Write, path))?;
+            let perms = fs::Permissions::from_mode(mode);
+            file.set_permissions(perms)
+                .map_err(Error::io(Op::Write, path))?;
+            return Ok(file);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    options.open(path).map_err(Error::io(Op::Write, path))
+}
+
+/// Removes the file at `path` for `T`, if it exists
+///
+/// This also removes its checksum, if `T::CHECKSUM` is set. If `T::TRASH` is set
+/// then the file is moved to the trash instead
+#[cfg_attr(
+    not(any(feature = "checksum", feature = "trash")),
+    allow(clippy::extra_unused_type_parameters)
+)]
+pub(crate) fn remove<T: Configurable>(path: &Path) -> Result<(), Error> {
+    writable::<T>(path)?;
+    #[cfg(feature = "checksum")]
+    {
+        if T::CHECKSUM {
+            remove_file(&sidecar(path, "sha256"))?;
+        }
+    }
+    #[cfg(feature = "trash")]
+    {
+        if T::TRASH {
+            if !path.exists() {
+                return Ok(());
+            }
+            return trash::delete(path).map_err(Error::Trash);
+        }
+    }
+    remove_file(path)
+}
+
+/// An `Error::ReadOnly` for `path` if `T` is read-only
+pub(crate) fn writable<T: Configurable>(path: &Path) -> Result<(), Error> {
+    match T::read_only() {
+        true => Err(Error::ReadOnly(path.to_owned())),
+        false => Ok(()),
+    }
+}
+
+/// The modified time and the length of the file at `path`, if it exists
+pub(crate) fn stamp(path: &Path) -> Option<(std::time::SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// The path of the `n`th backup of the file at `path` (e.g. `config.toml.1`)
+pub(crate) fn backup_path(path: &Path, n: usize) -> PathBuf {
+    sidecar(path, &n.to_string())
+}
+
+/// The path of the file at `path` with `ext` appended (e.g. `config.toml.sha256`)
+fn sidecar(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", ext));
+    path.with_file_name(name)
+}
+
+fn remove_file(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        res => res.map_err(Error::io(Op::Remove, path)),
+    }
+}
+
+/// Whether the file at `path` already contains exactly `data`
+fn unchanged(path: &Path, data: &[u8]) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() == data.len() as u64 => {
+            fs::read(path).map(|old| old == data).unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+/// Shifts `path.1 .. path.{count-1}` up by one, then copies `path` to `path.1`
+fn rotate(path: &Path, count: usize) -> Result<(), Error> {
+    for n in (1..count).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            let to = backup_path(path, n + 1);
+            fs::rename(&from, to).map_err(Error::io(Op::Rename, &from))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1)).map_err(Error::io(Op::Copy, path))?;
+    Ok(())
+}
+
+/// The hex-encoded SHA-256 of `data`
+#[cfg(feature = "checksum")]
+fn digest(data: &[u8]) -> String {
+    use sha2::Digest as _;
+    sha2::Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Verifies `data` against the checksum of the file at `path`
+///
+/// A missing checksum is not an error, as there is nothing to verify against
+#[cfg(feature = "checksum")]
+fn verify(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let sidecar = sidecar(path, "sha256");
+    let sum = match fs::read_to_string(&sidecar) {
+        Ok(sum) => sum,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(Error::io(Op::Read, &sidecar)(err)),
+    };
+    match sum.split_whitespace().next() {
+        Some(sum) if sum == digest(data) => Ok(()),
+        _ => Err(Error::Corrupt(path.to_owned())),
+    }
+}
@@ -0,0 +1,752 @@
+use super::*;
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Read;
+use std::marker::PhantomData;
+
+/// A serialization format used by `Configurable` to load and save a type
+///
+/// ```
+/// use configurable::{Error, Format};
+///
+/// // A format which stores the value as TOML, but with a header
+/// struct Headered;
+///
+/// impl Format for Headered {
+///     fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+///         let body = toml::to_string(value).map_err(Error::TomlWrite)?;
+///         Ok(format!("# generated\n{}", body).into_bytes())
+///     }
+///
+///     fn deserialize<T: serde::de::DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+///         toml::from_slice(data).map_err(Error::TomlRead)
+///     }
+/// }
+/// ```
+pub trait Format {
+    /// Serializes the value into bytes
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error>;
+
+    /// Serializes the value into bytes, using the `SaveOptions`
+    ///
+    /// By default the options are ignored
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        let _ = options;
+        Self::serialize(value)
+    }
+
+    /// Deserializes a value from bytes
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error>;
+
+    /// Deserializes a value from the reader
+    ///
+    /// By default this reads everything, then uses `deserialize`. Formats which
+    /// can decode incrementally override this
+    fn deserialize_reader<T: DeserializeOwned>(mut reader: impl Read) -> Result<T, Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        Self::deserialize(&data)
+    }
+}
+
+/// Deserializes a value, adding the path of the field which failed to the error
+///
+/// This is for formats whose errors only have a position (TOML errors already
+/// have the key). Errors at the top-level are kept as they are, see `Error::Field`
+#[cfg(any(feature = "json", feature = "yaml"))]
+fn tracked<'de, D, T>(de: D, wrap: fn(D::Error) -> Error) -> Result<T, Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde_path_to_error::deserialize(de).map_err(|err| {
+        let field = err.path().to_string();
+        let source = Box::new(wrap(err.into_inner()));
+        match field.as_str() {
+            "" | "." => *source,
+            _ => Error::Field { field, source },
+        }
+    })
+}
+
+/// Options for how a `Format` writes a value
+///
+/// Formats use the options they understand, and ignore the rest.
+/// The default is `SaveOptions::pretty()`
+///
+/// ```
+/// use configurable::{Format, SaveOptions, TableStyle, TomlFormat};
+///
+/// #[derive(serde::Serialize)]
+/// struct Foo { list: Vec<i32>, server: Server }
+///
+/// #[derive(serde::Serialize)]
+/// struct Server { port: u16 }
+///
+/// let foo = Foo { list: vec![1, 2], server: Server { port: 80 } };
+///
+/// let options = SaveOptions::pretty().multiline_arrays(false);
+/// let out = TomlFormat::serialize_with(&foo, &options).unwrap();
+/// assert_eq!(out, b"list = [1, 2]\n\n[server]\nport = 80\n");
+///
+/// let options = options.tables(TableStyle::Inline);
+/// let out = TomlFormat::serialize_with(&foo, &options).unwrap();
+/// assert_eq!(out, b"list = [1, 2]\nserver = { port = 80 }\n");
+///
+/// let options = options.tables(TableStyle::Dotted);
+/// let out = TomlFormat::serialize_with(&foo, &options).unwrap();
+/// assert_eq!(out, b"list = [1, 2]\nserver.port = 80\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveOptions {
+    pub(crate) pretty: bool,
+    pub(crate) multiline_strings: bool,
+    pub(crate) literal_strings: bool,
+    pub(crate) multiline_arrays: bool,
+    pub(crate) indent: Option<usize>,
+    pub(crate) trailing_comma: bool,
+    pub(crate) tables: TableStyle,
+    pub(crate) array_of_tables: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self::pretty()
+    }
+}
+
+impl SaveOptions {
+    /// Human-readable output (this is what `toml::to_string_pretty` produces)
+    pub fn pretty() -> Self {
+        Self {
+            pretty: true,
+            multiline_strings: true,
+            literal_strings: true,
+            multiline_arrays: true,
+            indent: None,
+            trailing_comma: true,
+            tables: TableStyle::Sections,
+            array_of_tables: true,
+        }
+    }
+
+    /// The most compact output the format supports
+    pub fn compact() -> Self {
+        Self {
+            pretty: false,
+            multiline_strings: false,
+            literal_strings: false,
+            multiline_arrays: false,
+            indent: Some(0),
+            trailing_comma: false,
+            tables: TableStyle::Sections,
+            array_of_tables: true,
+        }
+    }
+
+    /// Whether strings containing newlines are written as multi-line strings
+    pub fn multiline_strings(mut self, value: bool) -> Self {
+        self.multiline_strings = value;
+        self
+    }
+
+    /// Whether strings are written as literal (`'...'`) strings when possible
+    ///
+    /// For TOML, this only applies to multi-line strings
+    pub fn literal_strings(mut self, value: bool) -> Self {
+        self.literal_strings = value;
+        self
+    }
+
+    /// Whether arrays are written with one element per line
+    pub fn multiline_arrays(mut self, value: bool) -> Self {
+        self.multiline_arrays = value;
+        self
+    }
+
+    /// How many spaces to indent nested values with
+    ///
+    /// By default each format uses its own (e.g. 2 for JSON, 4 for TOML)
+    pub fn indent(mut self, value: usize) -> Self {
+        self.indent = Some(value);
+        self
+    }
+
+    /// Whether multi-line arrays have a trailing comma
+    pub fn trailing_comma(mut self, value: bool) -> Self {
+        self.trailing_comma = value;
+        self
+    }
+
+    /// How tables nested in other tables are written
+    pub fn tables(mut self, value: TableStyle) -> Self {
+        self.tables = value;
+        self
+    }
+
+    /// Whether arrays of tables are written as `[[name]]` sections, rather than
+    /// as arrays of inline tables
+    pub fn array_of_tables(mut self, value: bool) -> Self {
+        self.array_of_tables = value;
+        self
+    }
+}
+
+/// How a TOML table nested in another table is written, see `SaveOptions::tables`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum TableStyle {
+    /// As a `[section]`
+    #[default]
+    Sections,
+    /// As an inline table, e.g. `server = { port = 80 }`
+    Inline,
+    /// As dotted keys, e.g. `server.port = 80`
+    Dotted,
+}
+
+/// A `Format` which reads with `R` and writes with `W`
+///
+/// ```
+/// # #[cfg(all(feature = "json", feature = "json5"))] {
+/// use configurable::{Format, JsonFormat, Json5Format, Split};
+///
+/// // read relaxed JSON5, but always write strict JSON
+/// type Relaxed = Split<Json5Format, JsonFormat>;
+///
+/// let list: Vec<i32> = Relaxed::deserialize(b"[1, 2, /* three */ 3,]").unwrap();
+/// assert_eq!(Relaxed::serialize(&list).unwrap(), b"[\n  1,\n  2,\n  3\n]");
+/// # }
+/// ```
+pub struct Split<R, W>(PhantomData<(R, W)>);
+
+impl<R: Format, W: Format> Format for Split<R, W> {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        W::serialize(value)
+    }
+
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        W::serialize_with(value, options)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        R::deserialize(data)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        R::deserialize_reader(reader)
+    }
+}
+
+/// The TOML format
+///
+/// Values are saved with `toml::to_string_pretty`
+///
+/// ```
+/// use configurable::{Format, SaveOptions, TomlFormat};
+///
+/// #[derive(serde::Serialize)]
+/// struct Foo { motd: &'static str }
+///
+/// let foo = Foo { motd: "hello\nworld" };
+/// let out = TomlFormat::serialize_with(&foo, &SaveOptions::pretty()).unwrap();
+/// assert_eq!(out, b"motd = '''\nhello\nworld'''\n");
+///
+/// let out = TomlFormat::serialize_with(&foo, &SaveOptions::compact()).unwrap();
+/// assert_eq!(out, b"motd = \"hello\\nworld\"\n");
+///
+/// let options = SaveOptions::pretty().multiline_strings(false);
+/// let out = TomlFormat::serialize_with(&foo, &options).unwrap();
+/// assert_eq!(out, b"motd = \"hello\\nworld\"\n");
+/// ```
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TomlFormat;
+
+impl Format for TomlFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        toml::to_string_pretty(value)
+            .map(String::into_bytes)
+            .map_err(Error::TomlWrite)
+    }
+
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        if options.tables != TableStyle::Sections || !options.array_of_tables {
+            // `toml` only writes sections, so the other styles are written here
+            let table = match toml::Value::try_from(value)? {
+                toml::Value::Table(table) => table,
+                _ => return Err(Error::TomlWrite(toml::ser::Error::UnsupportedType)),
+            };
+            let mut writer = TomlWriter {
+                options,
+                out: String::new(),
+            };
+            writer.section(&[], &table, None)?;
+            return Ok(writer.out.into_bytes());
+        }
+        let mut out = String::new();
+        value.serialize(&mut toml_serializer(&mut out, options))?;
+        Ok(out.into_bytes())
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        toml::from_slice(data).map_err(Error::TomlRead)
+    }
+}
+
+/// A `toml::Serializer` for the string and array options
+fn toml_serializer<'a>(out: &'a mut String, options: &SaveOptions) -> toml::Serializer<'a> {
+    let mut ser = if options.pretty {
+        toml::Serializer::pretty(out)
+    } else {
+        toml::Serializer::new(out)
+    };
+    // `toml` only writes literal strings as multi-line strings, and setting
+    // `pretty_string_literal` turns those on
+    if options.multiline_strings {
+        ser.pretty_string(true)
+            .pretty_string_literal(options.literal_strings);
+    } else {
+        ser.pretty_string(false);
+    }
+    ser.pretty_array(options.multiline_arrays);
+    // these enable multi-line arrays, so only set them when they're wanted
+    if options.multiline_arrays {
+        ser.pretty_array_indent(options.indent.unwrap_or(4))
+            .pretty_array_trailing_comma(options.trailing_comma);
+    }
+    ser
+}
+
+/// Writes TOML with the `TableStyle` and `array_of_tables` of the options
+struct TomlWriter<'a> {
+    options: &'a SaveOptions,
+    out: String,
+}
+
+impl TomlWriter<'_> {
+    /// Writes the table at `path`, under the `header` (none for the top-level)
+    fn section(
+        &mut self,
+        path: &[String],
+        table: &toml::value::Table,
+        header: Option<&str>,
+    ) -> Result<(), Error> {
+        enum Nested<'t> {
+            Table(&'t toml::value::Table),
+            Tables(&'t [toml::Value]),
+        }
+
+        let (mut lines, mut nested) = (vec![], vec![]);
+        for (key, value) in table {
+            match value {
+                toml::Value::Table(table) if self.options.tables == TableStyle::Sections => {
+                    nested.push((key, Nested::Table(table)))
+                }
+                toml::Value::Table(table)
+                    if self.options.tables == TableStyle::Dotted && !table.is_empty() =>
+                {
+                    self.dotted(&toml_key(key), table, &mut lines)?
+                }
+                toml::Value::Array(array) if self.options.array_of_tables && is_tables(array) => {
+                    nested.push((key, Nested::Tables(array)))
+                }
+                value => lines.push(format!("{} = {}", toml_key(key), self.inline(value)?)),
+            }
+        }
+
+        // like `toml`, a section with only sections in it doesn't get a header
+        let header = header
+            .filter(|header| header.starts_with("[[") || !lines.is_empty() || nested.is_empty());
+        if let Some(header) = header {
+            if !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            self.out.push_str(header);
+            self.out.push('\n');
+        }
+        for line in lines {
+            self.out.push_str(&line);
+            self.out.push('\n');
+        }
+
+        for (key, nested) in nested {
+            let mut path = path.to_vec();
+            path.push(toml_key(key));
+            let name = path.join(".");
+            match nested {
+                Nested::Table(table) => self.section(&path, table, Some(&format!("[{}]", name)))?,
+                Nested::Tables(tables) => {
+                    let header = format!("[[{}]]", name);
+                    for table in tables.iter().filter_map(toml::Value::as_table) {
+                        self.section(&path, table, Some(&header))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the values of the table as `prefix.key = value`
+    fn dotted(
+        &self,
+        prefix: &str,
+        table: &toml::value::Table,
+        lines: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        for (key, value) in table {
+            let key = format!("{}.{}", prefix, toml_key(key));
+            match value {
+                toml::Value::Table(table) if !table.is_empty() => {
+                    self.dotted(&key, table, lines)?
+                }
+                value => lines.push(format!("{} = {}", key, self.inline(value)?)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a value on the right-hand side of a key, tables are written inline
+    fn inline(&self, value: &toml::Value) -> Result<String, Error> {
+        match value {
+            toml::Value::Table(table) if table.is_empty() => Ok("{}".to_string()),
+            toml::Value::Table(table) => {
+                let pairs = table
+                    .iter()
+                    .map(|(key, value)| Ok(format!("{} = {}", toml_key(key), self.inline(value)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(format!("{{ {} }}", pairs.join(", ")))
+            }
+            toml::Value::Array(array) if array.iter().any(toml::Value::is_table) => {
+                let values = array
+                    .iter()
+                    .map(|value| self.inline(value))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(format!("[{}]", values.join(", ")))
+            }
+            value => {
+                let mut out = String::new();
+                value.serialize(&mut toml_serializer(&mut out, self.options))?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Whether the array is an array of tables
+fn is_tables(array: &[toml::Value]) -> bool {
+    !array.is_empty() && array.iter().all(toml::Value::is_table)
+}
+
+/// A TOML key, quoted unless it is a bare key
+fn toml_key(key: &str) -> String {
+    let bare = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if !key.is_empty() && key.chars().all(bare) {
+        return key.to_string();
+    }
+    toml::Value::String(key.to_string()).to_string()
+}
+
+/// The JSON format
+///
+/// Values are saved with `serde_json::to_vec_pretty`
+#[cfg(feature = "json")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct JsonFormat;
+
+#[cfg(feature = "json")]
+impl Format for JsonFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec_pretty(value).map_err(Error::JsonWrite)
+    }
+
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        if !options.pretty {
+            return serde_json::to_vec(value).map_err(Error::JsonWrite);
+        }
+        let mut out = vec![];
+        let indent = " ".repeat(options.indent.unwrap_or(2));
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+        value.serialize(&mut ser).map_err(Error::JsonWrite)?;
+        Ok(out)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        let mut de = serde_json::Deserializer::from_slice(data);
+        let value = tracked(&mut de, Error::JsonRead)?;
+        de.end().map_err(Error::JsonRead)?;
+        Ok(value)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let value = tracked(&mut de, Error::JsonRead)?;
+        de.end().map_err(Error::JsonRead)?;
+        Ok(value)
+    }
+}
+
+/// The YAML format
+///
+/// Values are saved with `serde_yaml::to_string`
+#[cfg(feature = "yaml")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct YamlFormat;
+
+#[cfg(feature = "yaml")]
+impl Format for YamlFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        serde_yaml::to_string(value)
+            .map(String::into_bytes)
+            .map_err(Error::YamlWrite)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        tracked(serde_yaml::Deserializer::from_slice(data), Error::YamlRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        tracked(
+            serde_yaml::Deserializer::from_reader(reader),
+            Error::YamlRead,
+        )
+    }
+}
+
+/// The RON (Rusty Object Notation) format
+///
+/// Values are saved with `ron::ser::to_string_pretty`
+#[cfg(feature = "ron")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RonFormat;
+
+#[cfg(feature = "ron")]
+impl Format for RonFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+            .map(String::into_bytes)
+            .map_err(Error::RonWrite)
+    }
+
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        let out = if options.pretty {
+            let config =
+                ron::ser::PrettyConfig::default().indentor(" ".repeat(options.indent.unwrap_or(4)));
+            ron::ser::to_string_pretty(value, config)
+        } else {
+            ron::ser::to_string(value)
+        };
+        out.map(String::into_bytes).map_err(Error::RonWrite)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        ron::de::from_bytes(data).map_err(Error::RonRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        ron::de::from_reader(reader).map_err(Error::RonRead)
+    }
+}
+
+/// The bincode binary format
+///
+/// This is not self-describing, so it is best suited for files in `Location::Data`
+#[cfg(feature = "bincode")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BincodeFormat;
+
+#[cfg(feature = "bincode")]
+impl Format for BincodeFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        bincode::serialize(value).map_err(Error::BincodeWrite)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        bincode::deserialize(data).map_err(Error::BincodeRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        bincode::deserialize_from(reader).map_err(Error::BincodeRead)
+    }
+}
+
+/// The MessagePack binary format
+///
+/// Structs are saved as maps (with `rmp_serde::to_vec_named`)
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MsgPackFormat;
+
+#[cfg(feature = "msgpack")]
+impl Format for MsgPackFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        rmp_serde::to_vec_named(value).map_err(Error::MsgPackWrite)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        rmp_serde::from_slice(data).map_err(Error::MsgPackRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        rmp_serde::from_read(reader).map_err(Error::MsgPackRead)
+    }
+}
+
+/// The CBOR binary format
+///
+/// Values are saved with `ciborium::into_writer`
+#[cfg(feature = "cbor")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CborFormat;
+
+#[cfg(feature = "cbor")]
+impl Format for CborFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        ciborium::into_writer(value, &mut out).map_err(Error::CborWrite)?;
+        Ok(out)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        ciborium::from_reader(data).map_err(Error::CborRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        ciborium::from_reader(reader).map_err(Error::CborRead)
+    }
+}
+
+/// The INI format
+///
+/// Sections map onto nested structs (`[server.tls]` is `server.tls`), and
+/// arrays are written as comma-separated values
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// use configurable::{Format, IniFormat};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Settings { name: String, server: Server }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Server { port: u16, hosts: Vec<String> }
+///
+/// let ini = "name = foo\n\n[server]\nport = 80\nhosts = a, b\n";
+/// let settings: Settings = IniFormat::deserialize(ini.as_bytes()).unwrap();
+/// assert_eq!(settings.server.port, 80);
+/// assert_eq!(settings.server.hosts, vec!["a", "b"]);
+///
+/// let out = IniFormat::serialize(&settings).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap(), ini);
+/// ```
+#[cfg(feature = "ini")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IniFormat;
+
+#[cfg(feature = "ini")]
+impl Format for IniFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        crate::ini::to_vec(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        crate::ini::from_slice(data)
+    }
+}
+
+/// The JSON5 format
+///
+/// This allows comments, trailing commas and unquoted keys. Combine it with
+/// `Split` to read JSON5 but write strict JSON
+#[cfg(feature = "json5")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Json5Format;
+
+#[cfg(feature = "json5")]
+impl Format for Json5Format {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        json5::to_string(value)
+            .map(String::into_bytes)
+            .map_err(Error::Json5Write)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        let data = std::str::from_utf8(data).map_err(|err| {
+            Error::Json5Read(json5::Error::Message {
+                msg: err.to_string(),
+                location: None,
+            })
+        })?;
+        json5::from_str(data).map_err(Error::Json5Read)
+    }
+}
+
+/// The KDL format
+///
+/// Tables are nodes with children, and values are the arguments of a node
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// use configurable::{Format, KdlFormat};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Settings { name: String, server: Server }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Server { port: u16, hosts: Vec<String> }
+///
+/// let kdl = "name foo\nserver {\n    port 80\n    hosts a b\n}\n";
+/// let settings: Settings = KdlFormat::deserialize(kdl.as_bytes()).unwrap();
+/// assert_eq!(settings.server.port, 80);
+/// assert_eq!(settings.server.hosts, vec!["a", "b"]);
+///
+/// let out = KdlFormat::serialize(&settings).unwrap();
+/// let again: Settings = KdlFormat::deserialize(&out).unwrap();
+/// assert_eq!(settings, again);
+/// ```
+#[cfg(feature = "kdl")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct KdlFormat;
+
+#[cfg(feature = "kdl")]
+impl Format for KdlFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        crate::kdl::to_vec(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        crate::kdl::from_slice(data)
+    }
+}
+
+/// The XML format
+///
+/// The root element is named after the type being saved
+#[cfg(feature = "xml")]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct XmlFormat;
+
+#[cfg(feature = "xml")]
+impl Format for XmlFormat {
+    fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+        Self::serialize_with(value, &SaveOptions::default())
+    }
+
+    fn serialize_with<T: Serialize>(value: &T, options: &SaveOptions) -> Result<Vec<u8>, Error> {
+        let mut out = String::new();
+        let mut ser = quick_xml::se::Serializer::new(&mut out);
+        if options.pretty {
+            ser.indent(' ', options.indent.unwrap_or(4));
+        }
+        value.serialize(ser).map_err(Error::XmlWrite)?;
+        Ok(out.into_bytes())
+    }
+
+    fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+        quick_xml::de::from_reader(data).map_err(Error::XmlRead)
+    }
+
+    fn deserialize_reader<T: DeserializeOwned>(reader: impl Read) -> Result<T, Error> {
+        let reader = std::io::BufReader::new(reader);
+        quick_xml::de::from_reader(reader).map_err(Error::XmlRead)
+    }
+}
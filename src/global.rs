@@ -0,0 +1,118 @@
+use super::*;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+/// A process-wide configuration, which is loaded on first use
+///
+/// Readers get an `Arc` of the current instance, `reload` and `swap` replace it
+/// for everyone without blocking readers which still hold the old one
+///
+/// Use `Configurable::global`, or a `static` of your own
+///
+/// ```no_run
+/// # use configurable::{Configurable, Global};
+/// # fn startup<T: Configurable + Send + Sync + 'static>() {
+/// // loads it, on the first use
+/// let config = T::global().get();
+///
+/// // e.g. on SIGHUP
+/// T::global().reload().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Global<T> {
+    value: RwLock<Option<Arc<T>>>,
+}
+
+impl<T> Default for Global<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Global<T> {
+    /// Creates an empty `Global`, for use in a `static`
+    pub const fn new() -> Self {
+        Self {
+            value: RwLock::new(None),
+        }
+    }
+}
+
+impl<T: Configurable> Global<T> {
+    /// The current instance, loading it if this is the first use
+    ///
+    /// # Panics
+    /// If the configuration cannot be loaded, use `try_get` to handle this
+    pub fn get(&self) -> Arc<T> {
+        self.try_get()
+            .unwrap_or_else(|err| panic!("cannot load configuration: {}", err))
+    }
+
+    /// The current instance, loading it if this is the first use (see `Configurable::load_or_default`)
+    ///
+    /// A file which cannot be parsed is an error, rather than using the defaults
+    pub fn try_get(&self) -> Result<Arc<T>, Error> {
+        if let Some(value) = &*self.value.read().unwrap_or_else(PoisonError::into_inner) {
+            return Ok(Arc::clone(value));
+        }
+
+        let mut value = self.value.write().unwrap_or_else(PoisonError::into_inner);
+        // another thread could've loaded it while this waited for the lock
+        if let Some(value) = &*value {
+            return Ok(Arc::clone(value));
+        }
+        let this = Arc::new(Self::load()?);
+        *value = Some(Arc::clone(&this));
+        Ok(this)
+    }
+
+    /// Loads the configuration again, replacing the current instance
+    ///
+    /// This loads it like `try_get`, so a missing file is the defaults. If it cannot
+    /// be loaded, the current instance is kept
+    pub fn reload(&self) -> Result<Arc<T>, Error> {
+        let this = Arc::new(Self::load()?);
+        self.swap(Arc::clone(&this));
+        Ok(this)
+    }
+
+    /// Replaces the current instance, returning the old one (if it was loaded)
+    pub fn swap(&self, value: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        let mut old = self.value.write().unwrap_or_else(PoisonError::into_inner);
+        old.replace(value.into())
+    }
+
+    /// Loads the configuration, or the defaults if the file doesn't exist
+    fn load() -> Result<T, Error> {
+        T::load_or_default()?.into_valid()
+    }
+}
+
+/// The `Global` for `T`, see `Configurable::global`
+pub(crate) fn global<T: Configurable + Send + Sync + 'static>() -> &'static Global<T> {
+    type Registry = RwLock<HashMap<TypeId, &'static (dyn Any + Send + Sync)>>;
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+    let registry = REGISTRY.get_or_init(Registry::default);
+    let id = TypeId::of::<T>();
+    let found = registry
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&id)
+        .copied();
+
+    let any = match found {
+        Some(any) => any,
+        None => *registry
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id)
+            // these live for the rest of the program, one per type
+            .or_insert_with(|| Box::leak(Box::new(Global::<T>::new()))),
+    };
+    any.downcast_ref()
+        .expect("the registry is keyed by the type id")
+}
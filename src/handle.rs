@@ -0,0 +1,95 @@
+use super::*;
+
+use std::ops::{Deref, DerefMut};
+
+/// A loaded configuration which is saved when it is dropped, if it was changed
+///
+/// Any mutable access marks it as changed. Errors from saving on drop are
+/// ignored, use `commit` to handle them
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use configurable::{Configurable, ConfigHandle, TomlFormat};
+/// # #[derive(Default, Serialize, Deserialize)]
+/// # struct Foo { name: String }
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "foobar";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+/// let mut foo = ConfigHandle::new(Foo::default());
+/// assert!(!foo.is_dirty());
+///
+/// foo.name = "bar".into();
+/// assert!(foo.is_dirty());
+///
+/// // this doesn't save it
+/// let foo = foo.into_inner();
+/// assert_eq!(foo.name, "bar");
+/// ```
+#[derive(Debug)]
+pub struct ConfigHandle<T: Configurable> {
+    value: T,
+    dirty: bool,
+}
+
+impl<T: Configurable> ConfigHandle<T> {
+    /// Wraps the instance, it is not considered changed
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            dirty: false,
+        }
+    }
+
+    /// Loads, or defaults the configuration (see `Configurable::load_or_default`)
+    ///
+    /// A file which cannot be parsed is an error, so the defaults aren't saved over it
+    pub fn load() -> Result<Self, Error> {
+        T::load_or_default()
+            .and_then(LoadState::into_valid)
+            .map(Self::new)
+    }
+
+    /// Whether the instance was changed since it was loaded, or last committed
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Saves the instance, if it was changed
+    pub fn commit(&mut self) -> Result<(), Error> {
+        if self.dirty {
+            self.value.save_mut()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Unwraps the instance, without saving it
+    pub fn into_inner(mut self) -> T {
+        self.dirty = false;
+        std::mem::take(&mut self.value)
+    }
+}
+
+impl<T: Configurable> Deref for ConfigHandle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: Configurable> DerefMut for ConfigHandle<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        &mut self.value
+    }
+}
+
+impl<T: Configurable> Drop for ConfigHandle<T> {
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
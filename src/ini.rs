@@ -0,0 +1,111 @@
+use super::*;
+
+use crate::de::Node;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Parses an INI document
+///
+/// Sections (`[a.b]`) map onto nested tables, and every value is a string
+/// which is parsed into the type the field expects
+pub(crate) fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+    let data = std::str::from_utf8(data).map_err(|err| Error::IniRead(err.to_string()))?;
+
+    let mut root = Node::Map(vec![]);
+    let mut section = vec![];
+    for (n, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = name.split('.').map(|s| s.trim().to_string()).collect();
+            if root.map_at(&section).is_none() {
+                let err = format!("line {}: `{}` is not a section", n + 1, name);
+                return Err(Error::IniRead(err));
+            }
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                let err = format!("line {}: expected `key = value`", n + 1);
+                return Err(Error::IniRead(err));
+            }
+        };
+        let value = value
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(value);
+
+        // this was already created when the section was entered
+        let map = root.map_at(&section).expect("section must exist");
+        match map.iter_mut().find(|(k, _)| k == key) {
+            Some((_, node)) => *node = Node::Leaf(value.to_string()),
+            None => map.push((key.to_string(), Node::Leaf(value.to_string()))),
+        }
+    }
+
+    T::deserialize(root).map_err(|err| Error::IniRead(err.to_string()))
+}
+
+/// Writes the value as an INI document
+///
+/// Nested tables become dotted sections, arrays become comma-separated values
+pub(crate) fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let value = toml::Value::try_from(value).map_err(|err| Error::IniWrite(err.to_string()))?;
+    let table = match value {
+        toml::Value::Table(table) => table,
+        _ => return Err(Error::IniWrite("top-level value must be a table".into())),
+    };
+
+    let mut out = String::new();
+    write_section(&mut out, &[], &table)?;
+    Ok(out.trim_start().to_string().into_bytes())
+}
+
+fn write_section(out: &mut String, path: &[&str], table: &toml::value::Table) -> Result<(), Error> {
+    let (tables, values): (Vec<_>, Vec<_>) = table.iter().partition(|(_, v)| v.is_table());
+
+    if !path.is_empty() && (!values.is_empty() || tables.is_empty()) {
+        out.push_str(&format!("\n[{}]\n", path.join(".")));
+    }
+    for (key, value) in values {
+        out.push_str(&format!("{} = {}\n", key, scalar(key, value)?));
+    }
+
+    for (key, value) in tables {
+        let mut path = path.to_vec();
+        path.push(key);
+        if let toml::Value::Table(table) = value {
+            write_section(out, &path, table)?;
+        }
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: &toml::Value) -> Result<String, Error> {
+    use toml::Value::*;
+    let s = match value {
+        String(s) if s.trim() != s => format!("\"{}\"", s),
+        String(s) => s.clone(),
+        Integer(i) => i.to_string(),
+        Float(f) => f.to_string(),
+        Boolean(b) => b.to_string(),
+        Datetime(d) => d.to_string(),
+        Array(list) => list
+            .iter()
+            .map(|v| match v {
+                Array(..) | Table(..) => Err(Error::IniWrite(format!(
+                    "`{}` cannot be nested in an array",
+                    key
+                ))),
+                v => scalar(key, v),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", "),
+        Table(..) => unreachable!("tables are written as sections"),
+    };
+    Ok(s)
+}
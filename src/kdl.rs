@@ -0,0 +1,131 @@
+use super::*;
+
+use crate::de::Node;
+use ::kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Parses a KDL document
+///
+/// * a node with children is a table (`server { port 80; }`)
+/// * a node with properties is a table (`server port=80`)
+/// * a node with arguments is a value, or a list of values (`hosts "a" "b"`)
+/// * a node whose children are all named `-` is a list (`servers { - { port 80; }; }`)
+pub(crate) fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
+    let data = std::str::from_utf8(data).map_err(|err| Error::KdlRead(err.to_string()))?;
+    let doc = KdlDocument::parse(data).map_err(|err| Error::KdlRead(err.to_string()))?;
+    T::deserialize(document(&doc)).map_err(|err| Error::KdlRead(err.to_string()))
+}
+
+/// Writes the value as a KDL document
+pub(crate) fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    let value = toml::Value::try_from(value).map_err(|err| Error::KdlWrite(err.to_string()))?;
+    let table = match value {
+        toml::Value::Table(table) => table,
+        _ => return Err(Error::KdlWrite("top-level value must be a table".into())),
+    };
+
+    let mut doc = KdlDocument::new();
+    for (key, value) in &table {
+        doc.nodes_mut().push(to_node(key, value));
+    }
+    doc.autoformat();
+    Ok(doc.to_string().into_bytes())
+}
+
+fn document(doc: &KdlDocument) -> Node {
+    let mut map: Vec<(String, Node)> = vec![];
+    for node in doc.nodes() {
+        let value = match entries(node) {
+            Some(value) => value,
+            None => continue,
+        };
+        let key = node.name().value();
+        match map.iter_mut().find(|(k, _)| k == key) {
+            Some((_, old)) => *old = value,
+            None => map.push((key.to_string(), value)),
+        }
+    }
+    Node::Map(map)
+}
+
+fn entries(node: &KdlNode) -> Option<Node> {
+    if let Some(children) = node.children() {
+        let nodes = children.nodes();
+        if !nodes.is_empty() && nodes.iter().all(|n| n.name().value() == "-") {
+            return Some(Node::Seq(nodes.iter().filter_map(entries).collect()));
+        }
+    }
+
+    let (props, args): (Vec<&KdlEntry>, Vec<&KdlEntry>) =
+        node.entries().iter().partition(|e| e.name().is_some());
+
+    if node.children().is_some() || !props.is_empty() {
+        let mut map = match node.children().map(document) {
+            Some(Node::Map(map)) => map,
+            _ => vec![],
+        };
+        for prop in props {
+            let key = prop
+                .name()
+                .map(|s| s.value().to_string())
+                .unwrap_or_default();
+            if let Some(value) = leaf(prop.value()) {
+                map.push((key, value));
+            }
+        }
+        return Some(Node::Map(map));
+    }
+
+    match args.as_slice() {
+        [arg] if arg.value().is_null() => None,
+        args => Some(Node::Seq(
+            args.iter().filter_map(|e| leaf(e.value())).collect(),
+        )),
+    }
+}
+
+fn leaf(value: &KdlValue) -> Option<Node> {
+    let s = match value {
+        KdlValue::String(s) => s.clone(),
+        KdlValue::Integer(i) => i.to_string(),
+        KdlValue::Float(f) => f.to_string(),
+        KdlValue::Bool(b) => b.to_string(),
+        KdlValue::Null => return None,
+    };
+    Some(Node::Leaf(s))
+}
+
+fn to_node(key: &str, value: &toml::Value) -> KdlNode {
+    let mut node = KdlNode::new(key);
+    match value {
+        toml::Value::Table(table) => {
+            let children = node.ensure_children();
+            for (key, value) in table {
+                children.nodes_mut().push(to_node(key, value));
+            }
+        }
+        toml::Value::Array(list) if list.iter().any(|v| v.is_table() || v.is_array()) => {
+            let children = node.ensure_children();
+            for value in list {
+                children.nodes_mut().push(to_node("-", value));
+            }
+        }
+        toml::Value::Array(list) => {
+            for value in list {
+                node.push(KdlEntry::new(to_value(value)));
+            }
+        }
+        value => node.push(KdlEntry::new(to_value(value))),
+    }
+    node
+}
+
+fn to_value(value: &toml::Value) -> KdlValue {
+    match value {
+        toml::Value::String(s) => KdlValue::String(s.clone()),
+        toml::Value::Integer(i) => KdlValue::Integer(i128::from(*i)),
+        toml::Value::Float(f) => KdlValue::Float(*f),
+        toml::Value::Boolean(b) => KdlValue::Bool(*b),
+        value => KdlValue::String(value.to_string()),
+    }
+}
@@ -0,0 +1,269 @@
+use super::*;
+
+use std::marker::PhantomData;
+
+/// Loads a configuration from layers, where each one overrides the ones before it
+///
+/// The layers are, in order:
+/// * the defaults, see `Configurable::load_embedded`
+/// * the system-wide file, in the directory of `SystemConfig`
+/// * the project-local file, if `local` is set
+/// * the file of the user, see `Configurable::load`
+/// * the `figment` providers, if any were added with `provider`
+/// * the environment variables, see `Configurable::ENV_OVERRIDES`
+/// * the overrides which were `set`, then the active `Overrides`
+///
+/// Tables are merged key by key. Missing files are skipped, and the errors of
+/// every file which cannot be loaded are returned together
+///
+/// ```no_run
+/// # use configurable::{Configurable, Layered};
+/// # fn load<T: Configurable>() {
+/// let config = Layered::<T>::new()
+///     .set("server.port", 8080)
+///     .load()
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Layered<T> {
+    system: bool,
+    local: bool,
+    user: bool,
+    env: bool,
+    overrides: Vec<(String, toml::Value)>,
+    #[cfg(feature = "figment")]
+    providers: Vec<(String, figment::Figment)>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for Layered<T> {
+    fn default() -> Self {
+        Self {
+            system: true,
+            local: false,
+            user: true,
+            env: true,
+            overrides: vec![],
+            #[cfg(feature = "figment")]
+            providers: vec![],
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Configurable> Layered<T> {
+    /// Creates a loader which uses every layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the system-wide file is used, defaults to `true`
+    pub fn system(mut self, system: bool) -> Self {
+        self.system = system;
+        self
+    }
+
+    /// Whether the project-local file is used, defaults to `false`
+    ///
+    /// This is found from the current directory, see `Configurable::find_local`
+    pub fn local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// Whether the file of the user is used, defaults to `true`
+    pub fn user(mut self, user: bool) -> Self {
+        self.user = user;
+        self
+    }
+
+    /// Whether the environment variables are used, defaults to `true`
+    ///
+    /// This doesn't need `Configurable::ENV_OVERRIDES` to be set
+    pub fn env(mut self, env: bool) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Overrides the value at the dotted `key` (e.g. `server.port`)
+    pub fn set(mut self, key: &str, value: impl Into<toml::Value>) -> Self {
+        self.overrides.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Adds a `figment::Provider` as a layer, after the file of the user
+    ///
+    /// Providers are used in the order they were added, with the `figment` feature
+    ///
+    /// ```no_run
+    /// # use configurable::{Configurable, Layered};
+    /// # fn load<T: Configurable>() {
+    /// let config = Layered::<T>::new()
+    ///     .provider(figment::providers::Serialized::default("server.port", 8080))
+    ///     .load()
+    ///     .unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "figment")]
+    pub fn provider(mut self, provider: impl figment::Provider) -> Self {
+        let name = provider.metadata().name.into_owned();
+        self.providers
+            .push((name, figment::Figment::from(provider)));
+        self
+    }
+
+    /// Loads the configuration from the layers
+    ///
+    /// If any files cannot be loaded, then their errors are in an `Error::Multiple`
+    pub fn load(&self) -> Result<T, Error> {
+        self.load_explained().map(|(this, _)| this)
+    }
+
+    /// Loads the configuration from the layers, along with where its values came from
+    ///
+    /// ```no_run
+    /// # use configurable::{Configurable, Layered};
+    /// # fn load<T: Configurable>() {
+    /// let (config, provenance) = Layered::<T>::new().load_explained().unwrap();
+    /// if let Some(origin) = provenance.provenance("server.port") {
+    ///     println!("the port is from {}", origin);
+    /// }
+    /// print!("{}", provenance.explain());
+    /// # }
+    /// ```
+    pub fn load_explained(&self) -> Result<(T, Provenance), Error> {
+        Env::once(|| {
+            let mut provenance = Provenance::default();
+            let mut value = toml::Value::try_from(T::load_embedded()?)?;
+            provenance.layer(&value, &Origin::Default);
+            for (origin, layer) in self.files()? {
+                provenance.layer(&layer, &origin);
+                value::merge(&mut value, layer);
+            }
+            let set = |value: &mut toml::Value, guess| self.apply(value, guess);
+            let (this, applied) = configurable::deserialize_parsed(value, set, |value, _| {
+                value.try_into().map_err(Error::TomlRead)
+            })?;
+            for (key, origin) in applied {
+                provenance.set(key, origin);
+            }
+            Ok((this, provenance))
+        })
+    }
+
+    /// Loads the configuration from the layers, combining them with `Merge`
+    ///
+    /// Each layer is merged over the ones before it with `Merge::merge_value`, so
+    /// only the values which it has are merged (e.g. with `#[merge(append)]`).
+    /// The environment variables and the overrides are used after that
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "derive")] {
+    /// # use configurable::{Configurable, Layered, Merge};
+    /// #[derive(Default, Merge, serde::Serialize, serde::Deserialize)]
+    /// #[serde(default)]
+    /// struct Daemon {
+    ///     port: Option<u16>,
+    ///     #[merge(append)]
+    ///     plugins: Vec<String>,
+    /// }
+    /// # impl Configurable for Daemon {
+    /// #     const ORGANIZATION: &'static str = "museun";
+    /// #     const APPLICATION: &'static str = "daemon";
+    /// #     const NAME: &'static str = "daemon.toml";
+    /// #     type Format = configurable::TomlFormat;
+    /// # }
+    ///
+    /// // the plugins of the system-wide file and the user's file are both used
+    /// let daemon = Layered::<Daemon>::new().load_merged().unwrap();
+    /// # }
+    /// ```
+    pub fn load_merged(&self) -> Result<T, Error>
+    where
+        T: Merge,
+    {
+        Env::once(|| {
+            let mut value = toml::Value::try_from(T::load_embedded()?)?;
+            let mut errors = vec![];
+            for (origin, layer) in self.files()? {
+                let mut merged = value.clone();
+                T::merge_value(&mut merged, layer);
+                // checked for each layer, so the error is for the file which has it
+                match (merged.clone().try_into::<T>(), origin) {
+                    (Ok(..), _) => value = merged,
+                    (Err(err), Origin::File(path)) => {
+                        errors.push(Error::in_file(&path)(Error::TomlRead(err)))
+                    }
+                    (Err(err), _) => errors.push(Error::TomlRead(err)),
+                }
+            }
+            if let Some(err) = Error::multiple(errors) {
+                return Err(err);
+            }
+            let set = |value: &mut toml::Value, guess| self.apply(value, guess);
+            configurable::deserialize_parsed(value, set, |value, _| {
+                value.try_into().map_err(Error::TomlRead)
+            })
+            .map(|(this, _)| this)
+        })
+    }
+
+    /// Reads the files (then the providers) which are used, skipping the missing ones
+    fn files(&self) -> Result<Vec<(Origin, toml::Value)>, Error> {
+        let mut paths = vec![];
+        if self.system {
+            let (organization, application) = configurable::identity::<T>()?;
+            let dir = configurable::system_dir(&organization, &application);
+            paths.push((dir.join(&*T::name()), false));
+        }
+        if self.local {
+            paths.extend(T::find_local().map(|path| (path, false)));
+        }
+        if self.user {
+            paths.push((T::try_path()?, true));
+        }
+        let (mut files, mut errors) = (vec![], vec![]);
+        for (path, secrets) in paths {
+            match configurable::read_layer::<T>(&path, secrets) {
+                Ok(Some(layer)) => files.push((Origin::File(path), layer)),
+                Ok(None) => {}
+                Err(err) => errors.push(err),
+            }
+        }
+        #[cfg(feature = "figment")]
+        for (name, figment) in &self.providers {
+            match figment.extract() {
+                Ok(layer) => files.push((Origin::Provider(name.clone()), layer)),
+                Err(err) => errors.push(Error::Figment(Box::new(err))),
+            }
+        }
+        match Error::multiple(errors) {
+            Some(err) => Err(err),
+            None => Ok(files),
+        }
+    }
+
+    /// Uses the environment variables, then the overrides (then the `Overrides`)
+    ///
+    /// `guess` is for the environment variables, see `configurable::env_overrides`.
+    /// Returns the dotted paths which were set, with where they came from
+    fn apply(&self, value: &mut toml::Value, guess: bool) -> Result<Vec<(String, Origin)>, Error> {
+        let mut applied = vec![];
+        if self.env {
+            if T::EXPAND_VARS {
+                configurable::expand_env(value)?;
+            }
+            let vars = configurable::env_overrides::<T>(value, guess)?;
+            applied.extend(vars.into_iter().map(|(path, var)| (path, Origin::Env(var))));
+        }
+        let overrides = self
+            .overrides
+            .iter()
+            .cloned()
+            .chain(Overrides::<T>::active());
+        let keys = overrides::apply(value, overrides.collect());
+        applied.extend(keys.into_iter().map(|key| (key, Origin::Override)));
+        Ok(applied)
+    }
+}
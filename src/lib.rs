@@ -3,7 +3,7 @@
 //!
 //! # A normal configuration (e.g. saving something to a toml file in CONFIG_DIR)
 //! ```
-//! use configurable::{Configurable, Config, Data, Error, LoadState};
+//! use configurable::{Configurable, LoadState, TomlFormat};
 //! use serde::{Serialize, Deserialize};
 //!
 //! #[derive(Serialize, Deserialize)]
@@ -23,22 +23,18 @@
 //!     }
 //! }
 //!
-//! impl Config for MyConfiguration {}
-//!
 //! impl Configurable for MyConfiguration {
 //!     const ORGANIZATION: &'static str = "museun";
 //!     const APPLICATION: &'static str = "foobar";
 //!     const NAME: &'static str = "config.toml";
-//!
-//!     fn ensure_dir() -> Result<std::path::PathBuf, Error> {
-//!         <Self as Config>::ensure_dir()
-//!     }
+//!     type Format = TomlFormat;
 //! }
 //! ```
 //!
 //! # A 'data' configuration (e.g. saving something to a json file in the DATA_DIR)
 //! ```
-//! use configurable::{Configurable, Config, Data, Error, LoadState};
+//! # #[cfg(feature = "json")] {
+//! use configurable::{Configurable, JsonFormat, LoadState, Location};
 //! use serde::{Serialize, Deserialize};
 //!
 //! #[derive(Default, Serialize, Deserialize)]
@@ -47,17 +43,14 @@
 //!     data: std::collections::HashMap<String, String>
 //! }
 //!
-//! impl Data for MyData {}
-//!
 //! impl Configurable for MyData {
 //!     const ORGANIZATION: &'static str = "museun";
 //!     const APPLICATION: &'static str = "foobar";
 //!     const NAME: &'static str = "data.json";
-//!
-//!     fn ensure_dir() -> Result<std::path::PathBuf, Error> {
-//!         <Self as Data>::ensure_dir()
-//!     }
+//!     const LOCATION: Location = Location::Data;
+//!     type Format = JsonFormat;
 //! }
+//! # }
 //! ```
 //!
 //! # Loading the data
@@ -82,6 +75,16 @@
 //!             );
 //!             std::process::exit(1)
 //!         }
+//!         // the file could not be parsed
+//!         configurable::LoadState::Invalid { error, default } => {
+//!             eprintln!("invalid configuration, using the defaults: {}", error);
+//!             default
+//!         }
+//!         // the file could not be parsed, but a backup could
+//!         configurable::LoadState::Recovered { error, backup, value } => {
+//!             eprintln!("invalid configuration, using {}: {}", backup.display(), error);
+//!             value
+//!         }
 //!     }
 //! }
 //! ```
@@ -93,16 +96,290 @@ pub enum LoadState<T> {
     Default(T),
     /// When the instance was loaded
     Loaded(T),
+    /// When the file could not be parsed, and the instance was defaulted
+    Invalid {
+        /// Why the file could not be parsed
+        error: Error,
+        /// The default instance
+        default: T,
+    },
+    /// When the file could not be parsed, and the instance was loaded from a backup
+    Recovered {
+        /// Why the file could not be parsed
+        error: Error,
+        /// The backup which was loaded
+        backup: std::path::PathBuf,
+        /// The recovered instance
+        value: T,
+    },
+}
+
+impl<T> LoadState<T> {
+    /// The instance, however it was created
+    pub fn into_inner(self) -> T {
+        match self {
+            LoadState::Default(value)
+            | LoadState::Loaded(value)
+            | LoadState::Invalid { default: value, .. }
+            | LoadState::Recovered { value, .. } => value,
+        }
+    }
+
+    /// The instance, or the error if the file could not be parsed (and it was `Invalid`)
+    ///
+    /// This is for instances which are saved later, so the defaults aren't saved over
+    /// the file
+    pub fn into_valid(self) -> Result<T, Error> {
+        match self {
+            LoadState::Invalid { error, .. } => Err(error),
+            this => Ok(this.into_inner()),
+        }
+    }
+
+    /// Whether the instance was loaded from the file
+    pub fn is_loaded(&self) -> bool {
+        matches!(self, LoadState::Loaded(..))
+    }
+
+    /// Whether the instance was defaulted, because the file was missing
+    pub fn is_default(&self) -> bool {
+        matches!(self, LoadState::Default(..))
+    }
+
+    /// The error from parsing the file, if it was `Invalid` or `Recovered`
+    pub fn error(&self) -> Option<&Error> {
+        match self {
+            LoadState::Invalid { error, .. } | LoadState::Recovered { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Maps the instance with `f`, keeping the state
+    pub fn map<U, F>(self, f: F) -> LoadState<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            LoadState::Default(value) => LoadState::Default(f(value)),
+            LoadState::Loaded(value) => LoadState::Loaded(f(value)),
+            LoadState::Invalid { error, default } => LoadState::Invalid {
+                error,
+                default: f(default),
+            },
+            LoadState::Recovered {
+                error,
+                backup,
+                value,
+            } => LoadState::Recovered {
+                error,
+                backup,
+                value: f(value),
+            },
+        }
+    }
+}
+
+impl<T: Configurable> LoadState<T> {
+    /// The instance, after saving it if it was defaulted (see `Configurable::save`)
+    ///
+    /// An `Invalid` or `Recovered` instance isn't saved, so the file is left for the
+    /// user to fix
+    ///
+    /// ```no_run
+    /// # use configurable::Configurable;
+    /// # fn load<T: Configurable>() -> Result<T, configurable::Error> {
+    /// let config = T::load_or_default()?.or_save()?;
+    /// # Ok(config)
+    /// # }
+    /// ```
+    pub fn or_save(self) -> Result<T, Error> {
+        if let LoadState::Default(value) = &self {
+            value.save()?;
+        }
+        Ok(self.into_inner())
+    }
 }
 
 mod configurable;
 #[doc(inline)]
-pub use self::configurable::{Config, Configurable, Data};
+pub use self::configurable::{Configurable, Detect, Location, SystemConfig};
+
+#[doc(inline)]
+#[allow(deprecated)]
+pub use self::configurable::{Config, Data};
+
+#[cfg(feature = "derive")]
+pub use configurable_derive::Configurable;
+
+#[doc(hidden)]
+pub mod cargo;
+
+mod config_file;
+#[doc(inline)]
+pub use self::config_file::ConfigFile;
+
+mod collection;
+#[doc(inline)]
+pub use self::collection::Collection;
 
 mod env;
 #[doc(inline)]
-pub use self::env::Env;
+pub use self::env::{Env, EnvBuilder, EnvGuard, EnvVars, PrefixOptions};
+
+mod layered;
+#[doc(inline)]
+pub use self::layered::Layered;
+
+mod overrides;
+#[doc(inline)]
+pub use self::overrides::{OverrideGuard, Overrides};
+
+mod provenance;
+#[doc(inline)]
+pub use self::provenance::{Origin, Provenance};
+
+#[doc(hidden)]
+pub mod merge;
+#[doc(inline)]
+pub use self::merge::Merge;
+
+#[cfg(feature = "derive")]
+pub use configurable_derive::Merge;
+
+mod diff;
+#[doc(inline)]
+pub use self::diff::ChangedKey;
+
+mod document;
+#[doc(inline)]
+pub use self::document::Document;
+
+mod documented;
+#[doc(inline)]
+pub use self::documented::Documented;
+
+mod handle;
+#[doc(inline)]
+pub use self::handle::ConfigHandle;
+
+mod shared;
+#[doc(inline)]
+pub use self::shared::Shared;
+
+mod global;
+#[doc(inline)]
+pub use self::global::Global;
+
+mod saver;
+#[doc(inline)]
+pub use self::saver::DebouncedSaver;
+
+mod watcher;
+#[doc(inline)]
+pub use self::watcher::Watcher;
+
+mod token;
+#[doc(inline)]
+pub use self::token::LoadToken;
+
+mod deprecation;
+#[doc(inline)]
+pub use self::deprecation::Deprecation;
+
+mod path;
+#[doc(inline)]
+pub use self::path::ConfigPath;
+
+mod format;
+#[doc(inline)]
+pub use self::format::{Format, SaveOptions, Split, TableStyle, TomlFormat};
+
+#[cfg(feature = "json")]
+#[doc(inline)]
+pub use self::format::JsonFormat;
+
+#[cfg(feature = "json5")]
+#[doc(inline)]
+pub use self::format::Json5Format;
+
+#[cfg(feature = "yaml")]
+#[doc(inline)]
+pub use self::format::YamlFormat;
+
+#[cfg(feature = "ron")]
+#[doc(inline)]
+pub use self::format::RonFormat;
+
+#[cfg(feature = "bincode")]
+#[doc(inline)]
+pub use self::format::BincodeFormat;
+
+#[cfg(feature = "msgpack")]
+#[doc(inline)]
+pub use self::format::MsgPackFormat;
+
+#[cfg(feature = "cbor")]
+#[doc(inline)]
+pub use self::format::CborFormat;
+
+#[cfg(feature = "ini")]
+#[doc(inline)]
+pub use self::format::IniFormat;
+
+#[cfg(feature = "kdl")]
+#[doc(inline)]
+pub use self::format::KdlFormat;
+
+#[cfg(feature = "xml")]
+#[doc(inline)]
+pub use self::format::XmlFormat;
+
+mod de;
+#[cfg(feature = "ini")]
+mod ini;
+#[cfg(feature = "kdl")]
+mod kdl;
+
+#[cfg(feature = "preserve")]
+mod preserve;
+
+#[cfg(feature = "tokio")]
+mod tokio;
+#[cfg(feature = "tokio")]
+#[doc(inline)]
+pub use self::tokio::AsyncConfigurable;
+
+#[cfg(feature = "clap")]
+mod cli;
+#[cfg(feature = "clap")]
+#[doc(inline)]
+pub use self::cli::CliConfigurable;
+
+#[cfg(feature = "figment")]
+mod provider;
+#[cfg(feature = "figment")]
+#[doc(inline)]
+pub use self::provider::ConfigProvider;
+
+#[cfg(feature = "schema")]
+mod schema;
+#[cfg(feature = "schema")]
+#[doc(inline)]
+pub use self::schema::Schema;
+
+#[cfg(feature = "miette")]
+mod diagnostic;
+
+mod snapshot;
+#[doc(inline)]
+pub use self::snapshot::Snapshot;
+
+mod file;
+#[doc(inline)]
+pub use self::file::FileLock;
+
+mod value;
 
 mod error;
 #[doc(inline)]
-pub use self::error::Error;
+pub use self::error::{EnvError, Error, ErrorKind, Op, Result};
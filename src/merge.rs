@@ -0,0 +1,260 @@
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::path::PathBuf;
+
+/// Combines a value with the one from a later layer, see `Layered::load_merged`
+///
+/// Structs merge each of their fields, `Option`s are only replaced by a `Some`,
+/// maps and sets are combined (merging the values of the same key), and any other
+/// value (including a `Vec`) is replaced
+///
+/// With the `derive` feature this can be derived for a struct:
+/// `#[derive(Merge)]`, where a field can have `#[merge(replace)]`, `#[merge(append)]`,
+/// or `#[merge(with = "path")]` (a `fn(&mut T, T)`)
+///
+/// The layers of `Layered::load_merged` are merged with `merge_value`, as a layer
+/// only has some of the values. A type which merges differently than its tables
+/// (e.g. appending a `Vec`) should implement it too, which is derived
+///
+/// ```
+/// use configurable::Merge;
+///
+/// struct Server {
+///     port: Option<u16>,
+///     hosts: Vec<String>,
+/// }
+///
+/// impl Merge for Server {
+///     fn merge(&mut self, other: Self) {
+///         self.port.merge(other.port);
+///         self.hosts.extend(other.hosts);
+///     }
+/// }
+///
+/// let mut server = Server { port: Some(80), hosts: vec!["a".into()] };
+/// server.merge(Server { port: None, hosts: vec!["b".into()] });
+/// assert_eq!(server.port, Some(80));
+/// assert_eq!(server.hosts, vec!["a", "b"]);
+/// ```
+pub trait Merge {
+    /// Merges `other` into this, where `other` takes precedence
+    fn merge(&mut self, other: Self);
+
+    /// Merges the `other` layer into `value`, where both are how this is serialized
+    ///
+    /// Only what is in `other` is merged, so the values it leaves out are kept.
+    /// Defaults to merging tables key by key, and replacing anything else
+    fn merge_value(value: &mut toml::Value, other: toml::Value)
+    where
+        Self: Sized,
+    {
+        value::merge(value, other)
+    }
+}
+
+macro_rules! replace {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Merge for $ty {
+                fn merge(&mut self, other: Self) {
+                    *self = other;
+                }
+            }
+        )*
+    };
+}
+
+replace! {
+    bool, char, String, PathBuf, ConfigPath, std::time::Duration,
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64,
+}
+
+impl<T> Merge for Vec<T> {
+    fn merge(&mut self, other: Self) {
+        *self = other;
+    }
+}
+
+impl<T: Merge> Merge for Option<T> {
+    fn merge(&mut self, other: Self) {
+        match (self.as_mut(), other) {
+            (Some(this), Some(other)) => this.merge(other),
+            (None, Some(other)) => *self = Some(other),
+            (_, None) => {}
+        }
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        T::merge_value(value, other)
+    }
+}
+
+impl<T: Merge> Merge for Box<T> {
+    fn merge(&mut self, other: Self) {
+        (**self).merge(*other)
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        T::merge_value(value, other)
+    }
+}
+
+impl<K, V, S> Merge for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Merge,
+    S: BuildHasher,
+{
+    fn merge(&mut self, other: Self) {
+        for (key, value) in other {
+            match self.get_mut(&key) {
+                Some(this) => this.merge(value),
+                None => {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        fields(value, other, V::merge_value)
+    }
+}
+
+impl<K: Ord, V: Merge> Merge for BTreeMap<K, V> {
+    fn merge(&mut self, other: Self) {
+        for (key, value) in other {
+            match self.get_mut(&key) {
+                Some(this) => this.merge(value),
+                None => {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        fields(value, other, V::merge_value)
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Merge for HashSet<T, S> {
+    fn merge(&mut self, other: Self) {
+        self.extend(other)
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        union(value, other)
+    }
+}
+
+impl<T: Ord> Merge for BTreeSet<T> {
+    fn merge(&mut self, other: Self) {
+        self.extend(other)
+    }
+
+    fn merge_value(value: &mut toml::Value, other: toml::Value) {
+        union(value, other)
+    }
+}
+
+impl Merge for toml::Value {
+    fn merge(&mut self, other: Self) {
+        value::merge(self, other)
+    }
+}
+
+// these are used by `#[derive(Merge)]`, for `Merge::merge_value`
+
+#[doc(hidden)]
+pub use serde::{de::DeserializeOwned, Serialize};
+#[doc(hidden)]
+pub use toml::Value;
+
+/// Merges the table `other` into `value`, with `field` for the keys which are in both
+#[doc(hidden)]
+pub fn fields<F>(value: &mut Value, other: Value, mut field: F)
+where
+    F: FnMut(&mut Value, Value),
+{
+    fields_by_key(value, other, |_, value, other| field(value, other))
+}
+
+/// Merges the table `other` into `value`, with `field` for the keys which are in both
+///
+/// `field` is given the key, so each one can be merged differently
+#[doc(hidden)]
+pub fn fields_by_key<F>(value: &mut Value, other: Value, mut field: F)
+where
+    F: FnMut(&str, &mut Value, Value),
+{
+    match (value, other) {
+        (Value::Table(table), Value::Table(other)) => {
+            for (key, other) in other {
+                match table.get_mut(&key) {
+                    Some(value) => field(&key, value, other),
+                    None => {
+                        table.insert(key, other);
+                    }
+                }
+            }
+        }
+        (value, other) => *value = other,
+    }
+}
+
+/// Merges `other` into `value`, like `Merge::merge_value` does by default
+#[doc(hidden)]
+pub fn deep(value: &mut Value, other: Value) {
+    value::merge(value, other)
+}
+
+/// Replaces `value` with `other`, for `#[merge(replace)]`
+#[doc(hidden)]
+pub fn replace(value: &mut Value, other: Value) {
+    *value = other
+}
+
+/// Appends the array `other` to `value`, for `#[merge(append)]`
+#[doc(hidden)]
+pub fn append(value: &mut Value, other: Value) {
+    match (value, other) {
+        (Value::Array(array), Value::Array(other)) => array.extend(other),
+        (value, other) => *value = other,
+    }
+}
+
+/// Merges `other` into `value` with `with` (for `#[merge(with = "path")]`), as `T`
+///
+/// If either of them isn't a `T` then they are merged like tables
+#[doc(hidden)]
+pub fn with<T>(value: &mut Value, other: Value, with: fn(&mut T, T))
+where
+    T: Serialize + DeserializeOwned,
+{
+    let merged = (|| {
+        let mut this = value.clone().try_into().ok()?;
+        with(&mut this, other.clone().try_into().ok()?);
+        Value::try_from(this).ok()
+    })();
+    match merged {
+        Some(merged) => *value = merged,
+        None => value::merge(value, other),
+    }
+}
+
+/// Appends the values of the array `other` which aren't in `value` already
+fn union(value: &mut Value, other: Value) {
+    match (value, other) {
+        (Value::Array(array), Value::Array(other)) => {
+            for value in other {
+                if !array.contains(&value) {
+                    array.push(value)
+                }
+            }
+        }
+        (value, other) => *value = other,
+    }
+}
@@ -0,0 +1,109 @@
+use super::*;
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Temporary overrides for a `Configurable`, which are used over every other layer
+///
+/// Each override is removed when its `OverrideGuard` is dropped. They are used by
+/// `Configurable::load` (and the other methods which load the file) and by `Layered`,
+/// after the environment variables. This is for tests, or tools which tweak a
+/// configuration for a while. The overrides are never saved: when an instance which
+/// was loaded is saved, the values which are still overridden are saved as they are
+/// in the file (other changes to them are saved)
+///
+/// These are shared by every thread, so tests which run at the same time can see
+/// the overrides of each other
+///
+/// ```
+/// # use configurable::{Configurable, Overrides, TomlFormat};
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct Foo { port: u16 }
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "overrides_test";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+/// let guard = Overrides::<Foo>::push("port", 8080);
+/// assert_eq!(Overrides::<Foo>::active(), vec![("port".to_string(), 8080.into())]);
+///
+/// drop(guard);
+/// assert!(Overrides::<Foo>::active().is_empty());
+/// ```
+pub struct Overrides<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Configurable> Overrides<T> {
+    /// Overrides the value at the dotted `key` (e.g. `server.port`), until the guard
+    /// is dropped
+    ///
+    /// Later overrides of the same key are used over the earlier ones
+    pub fn push(key: &str, value: impl Into<toml::Value>) -> OverrideGuard {
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        stack().push(Entry {
+            id,
+            target: target::<T>(),
+            key: key.to_string(),
+            value: value.into(),
+        });
+        OverrideGuard { id }
+    }
+
+    /// The overrides which are active, in the order they were pushed
+    pub fn active() -> Vec<(String, toml::Value)> {
+        let target = target::<T>();
+        stack()
+            .iter()
+            .filter(|entry| entry.target == target)
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect()
+    }
+}
+
+/// An override from `Overrides::push`, it is removed when this is dropped
+#[must_use = "the override is removed when this is dropped"]
+#[derive(Debug)]
+pub struct OverrideGuard {
+    id: u64,
+}
+
+impl Drop for OverrideGuard {
+    fn drop(&mut self) {
+        stack().retain(|entry| entry.id != self.id);
+    }
+}
+
+struct Entry {
+    id: u64,
+    target: (String, String, String),
+    key: String,
+    value: toml::Value,
+}
+
+static STACK: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+fn stack() -> std::sync::MutexGuard<'static, Vec<Entry>> {
+    STACK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Which configuration an override is for
+fn target<T: Configurable>() -> (String, String, String) {
+    let (org, app, name) = (T::organization(), T::application(), T::name());
+    (org.into_owned(), app.into_owned(), name.into_owned())
+}
+
+/// Applies the `overrides` to `value`, returning their keys
+pub(crate) fn apply(value: &mut toml::Value, overrides: Vec<(String, toml::Value)>) -> Vec<String> {
+    let mut applied = vec![];
+    for (key, item) in overrides {
+        value::insert(value, &key.split('.').collect::<Vec<_>>(), item);
+        applied.push(key);
+    }
+    applied
+}
@@ -0,0 +1,77 @@
+use super::*;
+
+use std::path::{Path, PathBuf};
+
+/// A path in a configuration, which can start with `~` or be relative to the configuration
+///
+/// This is saved as it was written, use `resolve` to get the path it refers to
+///
+/// ```
+/// # use configurable::ConfigPath;
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Foo { music: ConfigPath, cache: ConfigPath }
+///
+/// let foo: Foo = toml::from_str("music = '~/music'\ncache = 'cache'").unwrap();
+/// assert_eq!(foo.cache.resolve_in("/etc/foo"), std::path::Path::new("/etc/foo/cache"));
+/// assert!(foo.music.expand().is_absolute());
+/// assert_eq!(foo.music.as_written(), std::path::Path::new("~/music"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct ConfigPath {
+    path: PathBuf,
+}
+
+impl ConfigPath {
+    /// Creates a path, from how it is written
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The path as it was written
+    pub fn as_written(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path with a leading `~` replaced by the home directory
+    ///
+    /// If there is no home directory, then this is the path as it was written
+    pub fn expand(&self) -> PathBuf {
+        let rest = match self.path.strip_prefix("~") {
+            Ok(rest) => rest,
+            Err(..) => return self.path.clone(),
+        };
+        match directories::BaseDirs::new() {
+            Some(dirs) => dirs.home_dir().join(rest),
+            None => self.path.clone(),
+        }
+    }
+
+    /// The path (see `expand`), relative to `dir` if it isn't absolute
+    pub fn resolve_in(&self, dir: impl AsRef<Path>) -> PathBuf {
+        dir.as_ref().join(self.expand())
+    }
+
+    /// The path (see `expand`), relative to the directory of `T` if it isn't absolute
+    pub fn resolve<T: Configurable>(&self) -> Result<PathBuf, Error> {
+        T::try_dir().map(|dir| self.resolve_in(dir))
+    }
+}
+
+impl From<PathBuf> for ConfigPath {
+    fn from(path: PathBuf) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&str> for ConfigPath {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<String> for ConfigPath {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// Patches the values of `updated` into the `existing` document
+///
+/// Values which are semantically unchanged keep their original formatting,
+/// and the comments/ordering of the existing document are kept
+pub(crate) fn patch(existing: &str, updated: &str) -> Result<String, Error> {
+    let mut doc = existing.parse::<DocumentMut>().map_err(Error::TomlEdit)?;
+    let updated = updated.parse::<DocumentMut>().map_err(Error::TomlEdit)?;
+    patch_table(doc.as_table_mut(), updated.as_table());
+    Ok(doc.to_string())
+}
+
+fn patch_table(old: &mut Table, new: &Table) {
+    let removed = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect::<Vec<_>>();
+    for key in removed {
+        old.remove(&key);
+    }
+
+    for (key, new) in new.iter() {
+        match (old.get_mut(key), new) {
+            (Some(Item::Table(old)), Item::Table(new)) => patch_table(old, new),
+            (Some(Item::Value(old)), Item::Value(new)) => patch_value(old, new),
+            (Some(old), new) if same_item(old, new) => {}
+            (Some(old), new) => *old = new.clone(),
+            (None, new) => {
+                old.insert(key, new.clone());
+            }
+        }
+    }
+}
+
+fn patch_value(old: &mut Value, new: &Value) {
+    if same_value(old, new) {
+        return;
+    }
+    let decor = old.decor().clone();
+    *old = new.clone();
+    *old.decor_mut() = decor;
+}
+
+fn same_value(left: &Value, right: &Value) -> bool {
+    let parse = |v: &Value| format!("v = {}", v.to_string().trim()).parse::<toml::Value>();
+    match (parse(left), parse(right)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
+
+fn same_item(left: &Item, right: &Item) -> bool {
+    let mut l = Table::new();
+    l.insert("v", left.clone());
+    let mut r = Table::new();
+    r.insert("v", right.clone());
+    let parse = |t: Table| DocumentMut::from(t).to_string().parse::<toml::Value>();
+    match (parse(l), parse(r)) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
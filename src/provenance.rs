@@ -0,0 +1,84 @@
+use super::*;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Where the values of a configuration came from, see `Layered::load_explained`
+///
+/// Values are dotted paths (e.g. `server.port`). Tables don't have an origin,
+/// only the values in them do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    origins: BTreeMap<String, Origin>,
+}
+
+impl Provenance {
+    /// Where the value at the dotted `key` came from
+    pub fn provenance(&self, key: &str) -> Option<&Origin> {
+        self.origins.get(key)
+    }
+
+    /// Every value and where it came from, sorted by their keys
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Origin)> + '_ {
+        self.origins
+            .iter()
+            .map(|(key, origin)| (key.as_str(), origin))
+    }
+
+    /// Every value and where it came from, one per line
+    ///
+    /// e.g. `server.port: /etc/foobar/config.toml`
+    pub fn explain(&self) -> String {
+        self.iter()
+            .map(|(key, origin)| format!("{}: {}\n", key, origin))
+            .collect()
+    }
+
+    /// Records the origin of every value in `layer`
+    pub(crate) fn layer(&mut self, layer: &toml::Value, origin: &Origin) {
+        for key in value::leaves(layer) {
+            self.set(key, origin.clone());
+        }
+    }
+
+    /// Records the origin of the value at `key`
+    ///
+    /// This drops the origins of the values it replaced (the values which were
+    /// in it, or the value it is in)
+    pub(crate) fn set(&mut self, key: String, origin: Origin) {
+        let nested = |a: &str, b: &str| a.strip_prefix(b).is_some_and(|s| s.starts_with('.'));
+        self.origins
+            .retain(|old, _| !nested(old, &key) && !nested(&key, old));
+        self.origins.insert(key, origin);
+    }
+}
+
+/// Where a value came from, see `Provenance`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Origin {
+    /// The defaults, see `Configurable::load_embedded`
+    Default,
+    /// A file
+    File(PathBuf),
+    /// An environment variable
+    Env(String),
+    /// A `figment::Provider`, with its name, see `Layered::provider`
+    #[cfg(feature = "figment")]
+    Provider(String),
+    /// An override, see `Layered::set`
+    Override,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "the defaults"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(key) => write!(f, "the environment variable `{}`", key),
+            #[cfg(feature = "figment")]
+            Origin::Provider(name) => write!(f, "the provider `{}`", name),
+            Origin::Override => write!(f, "an override"),
+        }
+    }
+}
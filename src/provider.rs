@@ -0,0 +1,93 @@
+use super::*;
+
+use figment::providers::Serialized;
+use figment::value::{Dict, Map};
+use figment::{Metadata, Profile, Provider};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+/// A `figment::Provider` for the file of a `Configurable`, with the `figment` feature
+///
+/// This reads the file like `Configurable::load` does (with its `SECRETS`, `FRAGMENTS`,
+/// `EXTENDS` and `INCLUDE`), without the environment variables. A missing file provides nothing
+///
+/// To use `figment` providers as layers, see `Layered::provider`
+///
+/// ```no_run
+/// # use configurable::{ConfigProvider, Configurable};
+/// # fn load<T: Configurable>() {
+/// let config: T = figment::Figment::new()
+///     .merge(ConfigProvider::<T>::system())
+///     .merge(ConfigProvider::<T>::new())
+///     .extract()
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ConfigProvider<T> {
+    system: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ConfigProvider<T> {
+    fn clone(&self) -> Self {
+        Self {
+            system: self.system,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Configurable> ConfigProvider<T> {
+    /// Provides the file of the user, see `Configurable::path`
+    pub fn new() -> Self {
+        Self {
+            system: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Provides the system-wide file, see `SystemConfig`
+    pub fn system() -> Self {
+        Self {
+            system: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The file which is provided
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        match self.system {
+            true => {
+                let (organization, application) = configurable::identity::<T>()?;
+                Ok(configurable::system_dir(&organization, &application).join(&*T::name()))
+            }
+            false => T::try_path(),
+        }
+    }
+}
+
+impl<T: Configurable> Default for ConfigProvider<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Configurable> Provider for ConfigProvider<T> {
+    fn metadata(&self) -> Metadata {
+        let metadata = Metadata::named(format!("{} configuration", T::application()));
+        match self.path() {
+            Ok(path) => metadata.source(path.as_path()),
+            Err(..) => metadata,
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        let path = self.path().map_err(|err| err.to_string())?;
+        match configurable::read_layer::<T>(&path, !self.system) {
+            Ok(Some(value)) => Serialized::defaults(value).data(),
+            Ok(None) => Ok(Map::new()),
+            Err(err) => Err(err.to_string().into()),
+        }
+    }
+}
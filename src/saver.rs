@@ -0,0 +1,127 @@
+use super::*;
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Saves a `Shared` configuration on a background thread, once it has stopped changing
+///
+/// Each `mark_dirty` restarts the quiet period, so a burst of changes is only
+/// written once. Pending changes are saved when this is dropped. This saves like
+/// `Shared::write_and_save`, so `Configurable::before_save` is called
+///
+/// ```no_run
+/// # use configurable::{Configurable, DebouncedSaver, Shared};
+/// # use std::time::Duration;
+/// # fn resize<T: Configurable + Send + Sync + 'static>(f: impl Fn(&mut T)) {
+/// let config = Shared::<T>::load().unwrap();
+/// let saver = DebouncedSaver::new(config.clone(), Duration::from_millis(500));
+///
+/// // e.g. on every resize event
+/// f(&mut config.write());
+/// saver.mark_dirty();
+///
+/// // saves it now, rather than waiting
+/// saver.flush().unwrap();
+/// # }
+/// ```
+pub struct DebouncedSaver<T> {
+    sender: mpsc::Sender<Message>,
+    error: Arc<Mutex<Option<Error>>>,
+    handle: Option<JoinHandle<()>>,
+    _marker: std::marker::PhantomData<fn() -> T>,
+}
+
+enum Message {
+    Dirty,
+    Flush(mpsc::Sender<()>),
+}
+
+impl<T: Configurable + Send + Sync + 'static> DebouncedSaver<T> {
+    /// Starts the background thread, which saves `shared` after `quiet` has passed
+    /// without a `mark_dirty`
+    pub fn new(shared: Shared<T>, quiet: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let error = Arc::new(Mutex::new(None));
+
+        let errors = Arc::clone(&error);
+        let save = move || {
+            if let Err(err) = shared.write_and_save(|_| {}) {
+                *errors.lock().unwrap_or_else(PoisonError::into_inner) = Some(err);
+            }
+        };
+
+        let handle = std::thread::spawn(move || {
+            let mut dirty = false;
+            loop {
+                let msg = if dirty {
+                    receiver.recv_timeout(quiet)
+                } else {
+                    receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                };
+                match msg {
+                    Ok(Message::Dirty) => dirty = true,
+                    Ok(Message::Flush(ack)) => {
+                        if std::mem::take(&mut dirty) {
+                            save()
+                        }
+                        let _ = ack.send(());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        dirty = false;
+                        save()
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        if dirty {
+                            save()
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            sender,
+            error,
+            handle: Some(handle),
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Marks the configuration as changed, it'll be saved after the quiet period
+    pub fn mark_dirty(&self) {
+        let _ = self.sender.send(Message::Dirty);
+    }
+
+    /// Saves any pending changes now, waiting for them to be written
+    ///
+    /// Returns the last error from saving in the background, if there was one
+    pub fn flush(&self) -> Result<(), Error> {
+        let (ack, wait) = mpsc::channel();
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = wait.recv();
+        }
+        match self
+            .error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> Drop for DebouncedSaver<T> {
+    fn drop(&mut self) {
+        // replacing the sender disconnects the thread, which saves anything pending
+        let (sender, _) = mpsc::channel();
+        drop(std::mem::replace(&mut self.sender, sender));
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
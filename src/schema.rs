@@ -0,0 +1,85 @@
+use super::*;
+
+use std::path::{Path, PathBuf};
+
+/// A Configurable type which writes a JSON Schema next to its file
+///
+/// The saved file references the schema so editors can offer completion and validation
+/// * TOML files get a `#:schema` header comment
+/// * YAML files get a `# yaml-language-server: $schema=` header comment
+/// * JSON files get a `$schema` key
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use configurable::{Configurable, Schema, TomlFormat};
+/// #[derive(Default, Serialize, Deserialize, schemars::JsonSchema)]
+/// struct Foo { name: String }
+///
+/// impl Schema for Foo {}
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "foobar";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+///
+/// assert_eq!(Foo::schema_file_name(), "config.schema.json");
+/// ```
+pub trait Schema: Configurable + schemars::JsonSchema {
+    /// The name of the schema file
+    ///
+    /// Defaults to the stem of `NAME` with `.schema.json` (e.g. `config.schema.json`)
+    fn schema_file_name() -> String {
+        let name = Self::name();
+        let stem = Path::new(&*name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&name);
+        format!("{}.schema.json", stem)
+    }
+
+    /// Tries to write the schema next to the file, returning its path
+    fn write_schema() -> Result<PathBuf, Error> {
+        let path = Self::try_dir()?.join(Self::schema_file_name());
+        crate::file::writable::<Self>(&path)?;
+        Self::ensure_dir()?;
+        let schema = schemars::schema_for!(Self);
+        let data = serde_json::to_vec_pretty(&schema).map_err(Error::JsonWrite)?;
+        crate::file::replace(&path, &data, None)?;
+        Ok(path)
+    }
+
+    /// Tries to save the configuration and its schema, with a reference to the schema
+    ///
+    /// The file is saved like `save`, with the reference added before it is written
+    fn save_with_schema(&self) -> Result<(), Error> {
+        Self::write_schema()?;
+
+        let (path, secrets) = (Self::try_path()?, configurable::secrets_path::<Self>(None)?);
+        let reference = format!("./{}", Self::schema_file_name());
+        let ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let header = match ext.as_str() {
+            "toml" => format!("#:schema {}\n", reference),
+            "yaml" | "yml" => format!("# yaml-language-server: $schema={}\n", reference),
+            "json" => {
+                return configurable::save_file_with(self, &path, &secrets, |data| {
+                    let mut value: serde_json::Value = serde_json::from_slice(&data)
+                        .map_err(Error::JsonRead)
+                        .map_err(Error::in_file(&path))?;
+                    if let Some(map) = value.as_object_mut() {
+                        map.insert("$schema".into(), reference.into());
+                    }
+                    JsonFormat::serialize_with(&value, &Self::save_options())
+                })
+            }
+            _ => return self.save(),
+        };
+        configurable::save_file_with(self, &path, &secrets, |data| {
+            Ok(header.into_bytes().into_iter().chain(data).collect())
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use super::*;
+
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A loaded configuration which is shared between threads
+///
+/// Clones refer to the same instance, so changes made through one (with
+/// `write_and_save` or `reload`) are seen by all of them
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # use configurable::{Configurable, Shared, TomlFormat};
+/// # #[derive(Default, Serialize, Deserialize)]
+/// # struct Foo { name: String }
+/// # impl Configurable for Foo {
+/// #     const ORGANIZATION: &'static str = "some_org";
+/// #     const APPLICATION: &'static str = "foobar";
+/// #     const NAME: &'static str = "config.toml";
+/// #     type Format = TomlFormat;
+/// # }
+/// let foo = Shared::new(Foo { name: "bar".into() });
+/// let other = foo.clone();
+/// std::thread::spawn(move || assert_eq!(other.read().name, "bar"))
+///     .join()
+///     .unwrap();
+/// ```
+pub struct Shared<T> {
+    inner: Arc<RwLock<T>>,
+    listeners: Arc<Mutex<Vec<Listener<T>>>>,
+}
+
+/// Called with the new instance, returns whether it should be kept
+type Listener<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            listeners: Arc::clone(&self.listeners),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        f.debug_struct("Shared").field("inner", &*value).finish()
+    }
+}
+
+impl<T: Configurable> Shared<T> {
+    /// Shares the instance
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(value)),
+            listeners: Arc::default(),
+        }
+    }
+
+    /// Loads, or defaults the configuration (see `Configurable::load_or_default`)
+    ///
+    /// A file which cannot be parsed is an error, so the defaults aren't saved over it
+    pub fn load() -> Result<Self, Error> {
+        T::load_or_default()
+            .and_then(LoadState::into_valid)
+            .map(Self::new)
+    }
+
+    /// Locks the instance for reading
+    ///
+    /// Writers are blocked until the guard is dropped
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the instance for writing, changes are not saved
+    ///
+    /// Readers and writers are blocked until the guard is dropped. Subscribers
+    /// are not notified of these changes
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Applies `f` to the instance, then saves it (see `Configurable::save_mut`)
+    ///
+    /// The instance is locked for writing, until it is saved
+    pub fn write_and_save<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut value = self.write();
+        f(&mut value);
+        value.save_mut()?;
+        drop(value);
+        self.notify();
+        Ok(())
+    }
+
+    /// Replaces the instance, without saving it
+    pub fn set(&self, value: T) {
+        *self.write() = value;
+        self.notify();
+    }
+
+    /// Loads the configuration from the file again, replacing the instance
+    ///
+    /// This is for when the file was changed by something else (e.g. from a
+    /// file watcher). If it cannot be loaded, the instance is left as is
+    pub fn reload(&self) -> Result<(), Error> {
+        let value = T::load()?;
+        self.set(value);
+        Ok(())
+    }
+
+    /// Subscribes to changes of the instance, as a `tokio` watch channel
+    ///
+    /// The receiver sees the changes from `write_and_save`, `set` and `reload`
+    #[cfg(feature = "tokio")]
+    pub fn watch_channel(&self) -> ::tokio::sync::watch::Receiver<Arc<T>>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        let (sender, receiver) = ::tokio::sync::watch::channel(Arc::new(self.read().clone()));
+        let listener = move |value: &T| sender.send(Arc::new(value.clone())).is_ok();
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(listener));
+        receiver
+    }
+
+    fn notify(&self) {
+        let value = self.read();
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|listener| listener(&value));
+    }
+}
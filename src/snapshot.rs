@@ -0,0 +1,102 @@
+use super::*;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A copy of the configuration file, made by `Configurable::snapshot`
+///
+/// These are stored in the `history` directory, next to the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    id: String,
+    path: PathBuf,
+    created: SystemTime,
+}
+
+impl Snapshot {
+    /// The id of the snapshot, used with `Configurable::restore`
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The path of the copy
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// When the snapshot was made
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    fn from_millis(history: &Path, name: &str, millis: u64) -> Self {
+        Self {
+            id: millis.to_string(),
+            path: history.join(format!("{}.{}", millis, name)),
+            created: UNIX_EPOCH + Duration::from_millis(millis),
+        }
+    }
+}
+
+/// The directory which snapshots of the files in `dir` are stored in
+pub(crate) fn history(dir: &Path) -> PathBuf {
+    dir.join("history")
+}
+
+/// Copies the file `name` in `dir` into its history
+pub(crate) fn create(dir: &Path, name: &str) -> Result<Snapshot, Error> {
+    let history = history(dir);
+    fs::create_dir_all(&history).map_err(Error::io(Op::CreateDir, &history))?;
+
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    // snapshots made in the same millisecond get the next free id
+    while history.join(format!("{}.{}", millis, name)).exists() {
+        millis += 1;
+    }
+
+    let snapshot = Snapshot::from_millis(&history, name, millis);
+    let path = dir.join(name);
+    let _lock = file::lock_shared(&path)?;
+    fs::copy(&path, &snapshot.path).map_err(Error::io(Op::Copy, &path))?;
+    Ok(snapshot)
+}
+
+/// The snapshots of the file `name` in `dir`, from oldest to newest
+pub(crate) fn list(dir: &Path, name: &str) -> Result<Vec<Snapshot>, Error> {
+    let history = history(dir);
+    let entries = match fs::read_dir(&history) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(Error::io(Op::Read, &history)(err)),
+    };
+
+    let mut list = vec![];
+    for entry in entries {
+        let entry = entry.map_err(Error::io(Op::Read, &history))?;
+        let file_name = entry.file_name();
+        let millis = file_name
+            .to_str()
+            .and_then(|s| s.split_once('.'))
+            .filter(|(_, rest)| *rest == name)
+            .and_then(|(millis, _)| millis.parse().ok());
+        if let Some(millis) = millis {
+            list.push(Snapshot::from_millis(&history, name, millis));
+        }
+    }
+    list.sort_by_key(|s| s.created);
+    Ok(list)
+}
+
+/// Finds the snapshot of the file `name` in `dir` with the `id`
+///
+/// An unknown `id` is an `Error::NotFound` of the file it would have
+pub(crate) fn find(dir: &Path, name: &str, id: &str) -> Result<Snapshot, Error> {
+    list(dir, name)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| Error::NotFound(history(dir).join(format!("{}.{}", id, name))))
+}
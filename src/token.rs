@@ -0,0 +1,58 @@
+use super::*;
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Identifies the version of the files a configuration was loaded from
+///
+/// This is the modified time and the length of every file which was read (e.g.
+/// the files it `EXTENDS` and `INCLUDE`s, and its `FRAGMENTS`), see
+/// `Configurable::load_if_modified`. The default token matches no files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadToken {
+    stamps: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<LoadToken>> = const { RefCell::new(None) };
+}
+
+impl LoadToken {
+    /// Runs `load`, recording the files (and directories) which it reads
+    pub(crate) fn record<R>(load: impl FnOnce() -> R) -> (R, Self) {
+        let outer = RECORDING.with(|recording| recording.replace(Some(Self::default())));
+        let res = load();
+        let token = RECORDING.with(|recording| recording.replace(outer));
+        (res, token.unwrap_or_default())
+    }
+
+    /// Records that the file (or the directory) at `path` is being read, if this
+    /// thread is recording
+    ///
+    /// This is before it is read, so a change while it is being read is seen later
+    pub(crate) fn read(path: &Path) {
+        RECORDING.with(|recording| {
+            if let Some(token) = &mut *recording.borrow_mut() {
+                if !token.stamps.iter().any(|(read, _)| read == path) {
+                    token.stamps.push((path.to_owned(), file::stamp(path)));
+                }
+            }
+        })
+    }
+
+    /// Whether none of the files changed (or were created, or removed) since they were read
+    pub(crate) fn is_current(&self) -> bool {
+        !self.stamps.is_empty()
+            && self
+                .stamps
+                .iter()
+                .all(|(path, stamp)| file::stamp(path) == *stamp)
+    }
+
+    /// The files (and directories) which were read
+    #[cfg_attr(not(feature = "notify"), allow(dead_code))]
+    pub(crate) fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.stamps.iter().map(|(path, _)| &**path)
+    }
+}
@@ -0,0 +1,212 @@
+use super::*;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// The async version of `Configurable`, for use inside of a `tokio` runtime
+///
+/// Each method runs its `Configurable` counterpart with `tokio::task::spawn_blocking`,
+/// so they behave the same (including `SECRETS`, `BACKUPS` and locking) without
+/// blocking the runtime. This is implemented for every `Configurable` type
+///
+/// Methods which take `&self` in `Configurable` take `self` here, and give it back
+/// along with the result (so the value isn't lost when they fail)
+///
+/// These are cancellation-safe. Once started, the blocking work runs to completion
+/// even if the future is dropped, and files are written to a temporary file which is
+/// then renamed over the old one. So a save is either fully written, or not at all
+/// (any temporary files left by a killed process are removed by a later save, once
+/// they are an hour old)
+///
+/// ```no_run
+/// # use configurable::{AsyncConfigurable, Configurable, LoadState};
+/// async fn bump<T: Configurable + Send + 'static>() -> Result<T, configurable::Error> {
+///     let this = match T::load_or_default_async().await? {
+///         LoadState::Loaded(this) => this,
+///         _ => T::default(),
+///     };
+///     let (this, saved) = this.save_async().await;
+///     saved.map(|_| this)
+/// }
+/// ```
+pub trait AsyncConfigurable: Configurable + Send + 'static {
+    /// See `Configurable::load_or_default`
+    fn load_or_default_async() -> impl Future<Output = Result<LoadState<Self>, Error>> + Send {
+        blocking(Self::load_or_default)
+    }
+
+    /// See `Configurable::load_or_create_default`
+    fn load_or_create_default_async(
+    ) -> impl Future<Output = Result<(LoadState<Self>, PathBuf), Error>> + Send {
+        blocking(Self::load_or_create_default)
+    }
+
+    /// See `Configurable::load_or_init`
+    fn load_or_init_async<F>(init: F) -> impl Future<Output = Result<LoadState<Self>, Error>> + Send
+    where
+        F: FnOnce() -> Result<Self, Error> + Send + 'static,
+    {
+        blocking(move || Self::load_or_init(init))
+    }
+
+    /// See `Configurable::load`
+    fn load_async() -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(Self::load)
+    }
+
+    /// See `Configurable::load_if_exists`
+    fn load_if_exists_async() -> impl Future<Output = Result<Option<Self>, Error>> + Send {
+        blocking(Self::load_if_exists)
+    }
+
+    /// See `Configurable::load_from_path`
+    fn load_from_path_async(path: PathBuf) -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(move || Self::load_from_path(path))
+    }
+
+    /// See `Configurable::load_profile`
+    fn load_profile_async(profile: String) -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(move || Self::load_profile(&profile))
+    }
+
+    /// See `Configurable::load_active_profile`
+    fn load_active_profile_async() -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(Self::load_active_profile)
+    }
+
+    /// See `Configurable::load_named`
+    fn load_named_async(name: String) -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(move || Self::load_named(&name))
+    }
+
+    /// See `Configurable::list_named`
+    fn list_named_async() -> impl Future<Output = Result<Vec<String>, Error>> + Send {
+        blocking(Self::list_named)
+    }
+
+    /// See `Configurable::save`
+    fn save_async(self) -> impl Future<Output = (Self, Result<(), Error>)> + Send {
+        blocking_with(self, |this| this.save())
+    }
+
+    /// See `Configurable::save_mut`
+    fn save_mut_async(self) -> impl Future<Output = (Self, Result<(), Error>)> + Send {
+        blocking_with(self, |this| this.save_mut())
+    }
+
+    /// See `Configurable::save_as`
+    fn save_as_async(
+        self,
+        path: PathBuf,
+    ) -> impl Future<Output = (Self, Result<(), Error>)> + Send {
+        blocking_with(self, move |this| this.save_as(path))
+    }
+
+    /// See `Configurable::save_named`
+    fn save_named_async(
+        self,
+        name: String,
+    ) -> impl Future<Output = (Self, Result<(), Error>)> + Send {
+        blocking_with(self, move |this| this.save_named(&name))
+    }
+
+    /// See `Configurable::save_preserving`
+    #[cfg(feature = "preserve")]
+    fn save_preserving_async(self) -> impl Future<Output = (Self, Result<(), Error>)> + Send {
+        blocking_with(self, |this| this.save_preserving())
+    }
+
+    /// See `Configurable::update`
+    fn update_async<F>(f: F) -> impl Future<Output = Result<Self, Error>> + Send
+    where
+        F: FnOnce(&mut Self) + Send + 'static,
+    {
+        blocking(move || Self::update(f))
+    }
+
+    /// See `Configurable::lock`
+    ///
+    /// The other async methods run on other threads, so they can take this lock
+    /// again (as can any thread of this process) while it is held
+    fn lock_async() -> impl Future<Output = Result<FileLock, Error>> + Send {
+        blocking(|| Self::lock().map(FileLock::for_process))
+    }
+
+    /// See `Configurable::exists`
+    fn exists_async() -> impl Future<Output = Result<bool, Error>> + Send {
+        blocking(Self::exists)
+    }
+
+    /// See `Configurable::delete`
+    fn delete_async() -> impl Future<Output = Result<(), Error>> + Send {
+        blocking(Self::delete)
+    }
+
+    /// See `Configurable::reset`
+    fn reset_async() -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(Self::reset)
+    }
+
+    /// See `Configurable::backups`
+    fn backups_async() -> impl Future<Output = Result<Vec<PathBuf>, Error>> + Send {
+        blocking(Self::backups)
+    }
+
+    /// See `Configurable::snapshot`
+    fn snapshot_async() -> impl Future<Output = Result<Snapshot, Error>> + Send {
+        blocking(Self::snapshot)
+    }
+
+    /// See `Configurable::list_snapshots`
+    fn list_snapshots_async() -> impl Future<Output = Result<Vec<Snapshot>, Error>> + Send {
+        blocking(Self::list_snapshots)
+    }
+
+    /// See `Configurable::restore`
+    fn restore_async(id: String) -> impl Future<Output = Result<Self, Error>> + Send {
+        blocking(move || Self::restore(&id))
+    }
+
+    /// See `Configurable::migrate_format`
+    fn migrate_format_async<F>(
+        old_name: String,
+    ) -> impl Future<Output = Result<Option<Self>, Error>> + Send
+    where
+        F: Format + 'static,
+    {
+        blocking(move || Self::migrate_format::<F>(&old_name))
+    }
+}
+
+impl<T: Configurable + Send + 'static> AsyncConfigurable for T {}
+
+/// Runs `f` on the blocking thread pool
+///
+/// Panics from `f` are resumed on the caller, and a cancelled task is an `Error::Cancelled`
+async fn blocking<F, R>(f: F) -> Result<R, Error>
+where
+    F: FnOnce() -> Result<R, Error> + Send + 'static,
+    R: Send + 'static,
+{
+    match ::tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(..) => Err(Error::Cancelled),
+    }
+}
+
+/// Runs `f` with `this` on the blocking thread pool, then gives `this` back
+///
+/// It is given back even if `f` fails, or if the task is cancelled before it runs
+async fn blocking_with<T, F>(this: T, f: F) -> (T, Result<(), Error>)
+where
+    T: Configurable + Send + 'static,
+    F: FnOnce(&mut T) -> Result<(), Error> + Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(this));
+    let task = Arc::clone(&slot);
+    let res = blocking(move || f(&mut task.lock().unwrap_or_else(PoisonError::into_inner))).await;
+    let this = std::mem::take(&mut *slot.lock().unwrap_or_else(PoisonError::into_inner));
+    (this, res)
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use toml::{value::Table, Value};
+
+/// Deeply merges `other` into `base`
+///
+/// Tables are merged key by key, any other value in `other` replaces the one in `base`
+pub(crate) fn merge(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Table(base), Value::Table(other)) => merge_table(base, other),
+        (base, other) => *base = other,
+    }
+}
+
+/// Deeply merges the `other` table into `base`
+pub(crate) fn merge_table(base: &mut Table, other: Table) {
+    for (key, value) in other {
+        match base.get_mut(&key) {
+            Some(old) => merge(old, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Removes the values at the dotted `paths` from `value`, returning them in a new table
+///
+/// The returned table keeps the nesting of the paths
+pub(crate) fn take_paths(value: &mut Value, paths: &[&str]) -> Value {
+    let mut out = Value::Table(Table::new());
+    for path in paths {
+        let parts = path.split('.').collect::<Vec<_>>();
+        if let Some(taken) = remove(value, &parts) {
+            insert(&mut out, &parts, taken);
+        }
+    }
+    out
+}
+
+/// Removes the value at the `path`
+pub(crate) fn remove(value: &mut Value, path: &[&str]) -> Option<Value> {
+    let (last, parents) = path.split_last()?;
+    let table = parents
+        .iter()
+        .try_fold(value, |value, part| value.get_mut(*part))?
+        .as_table_mut()?;
+    table.remove(*last)
+}
+
+/// Inserts the value at the `path`, creating any missing tables
+pub(crate) fn insert(value: &mut Value, path: &[&str], item: Value) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+    let mut value = value;
+    for part in parents {
+        let table = match value {
+            Value::Table(table) => table,
+            _ => return,
+        };
+        value = table
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+    }
+    if let Value::Table(table) = value {
+        table.insert(last.to_string(), item);
+    }
+}
+
+/// Puts the values of `raw` back in `value`, where it has the same value as `loaded`
+/// (which is `raw` with other layers over it)
+///
+/// This undoes the layers, but keeps the values which were changed since it was
+/// loaded. Returns `false` if the value should be removed, as `raw` doesn't have it
+pub(crate) fn unlayer(value: &mut Value, loaded: &Value, raw: Option<&Value>) -> bool {
+    if raw == Some(loaded) {
+        return true;
+    }
+    if value == loaded {
+        return match raw {
+            Some(raw) => {
+                *value = raw.clone();
+                true
+            }
+            None => false,
+        };
+    }
+    match (value, loaded) {
+        (Value::Table(table), Value::Table(loaded)) => {
+            let raw = raw.and_then(Value::as_table);
+            // rebuilt, as removing from the table would reorder it
+            *table = std::mem::take(table)
+                .into_iter()
+                .filter_map(|(key, mut value)| {
+                    let keep = match loaded.get(&key) {
+                        Some(loaded) => {
+                            unlayer(&mut value, loaded, raw.and_then(|raw| raw.get(&key)))
+                        }
+                        None => true,
+                    };
+                    Some((key, value)).filter(|_| keep)
+                })
+                .collect();
+        }
+        (Value::Array(array), Value::Array(loaded)) if array.len() == loaded.len() => {
+            // elements can't be removed, so they are only undone where `raw` has them
+            if let Some(raw) = raw
+                .and_then(Value::as_array)
+                .filter(|raw| raw.len() == loaded.len())
+            {
+                for ((value, loaded), raw) in array.iter_mut().zip(loaded).zip(raw) {
+                    unlayer(value, loaded, Some(raw));
+                }
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
+/// The dotted paths of the values in `value`, skipping the tables which aren't empty
+pub(crate) fn leaves(value: &Value) -> Vec<String> {
+    fn walk(prefix: &str, value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Table(table) if !table.is_empty() => {
+                for (key, value) in table {
+                    let key = match prefix {
+                        "" => key.clone(),
+                        prefix => format!("{}.{}", prefix, key),
+                    };
+                    walk(&key, value, out);
+                }
+            }
+            _ if !prefix.is_empty() => out.push(prefix.to_string()),
+            _ => {}
+        }
+    }
+
+    let mut out = vec![];
+    walk("", value, &mut out);
+    out
+}
+
+/// Applies the json merge `patch` (RFC 7386) to `value`, returning the dotted paths
+/// which were changed
+///
+/// A `null` resets the value to the one in `defaults`, or removes it if there isn't one
+#[cfg(feature = "json")]
+pub(crate) fn merge_patch(
+    value: &mut Value,
+    patch: serde_json::Value,
+    defaults: Option<&Value>,
+) -> Result<Vec<String>, toml::ser::Error> {
+    fn walk(
+        prefix: &str,
+        value: &mut Value,
+        patch: serde_json::Map<String, serde_json::Value>,
+        defaults: Option<&Value>,
+        out: &mut Vec<String>,
+    ) -> Result<(), toml::ser::Error> {
+        if !value.is_table() {
+            *value = Value::Table(Table::new());
+        }
+        let table = value.as_table_mut().expect("value is a table");
+        for (key, patch) in patch {
+            let path = match prefix {
+                "" => key.clone(),
+                prefix => format!("{}.{}", prefix, key),
+            };
+            let default = defaults.and_then(|defaults| defaults.get(&key));
+            match patch {
+                serde_json::Value::Null => {
+                    match default {
+                        Some(default) => table.insert(key, default.clone()),
+                        None => table.remove(&key),
+                    };
+                    out.push(path);
+                }
+                serde_json::Value::Object(patch) => {
+                    let value = table
+                        .entry(key)
+                        .or_insert_with(|| Value::Table(Table::new()));
+                    walk(&path, value, patch, default, out)?;
+                }
+                patch => {
+                    table.insert(key, Value::try_from(patch)?);
+                    out.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let mut out = vec![];
+    match patch {
+        serde_json::Value::Object(patch) => walk("", value, patch, defaults, &mut out)?,
+        patch => *value = Value::try_from(patch)?,
+    }
+    Ok(out)
+}
+
+/// Moves the value at the dotted path `old` to `new`, returning whether there was one
+///
+/// If `new` already has a value then it is kept, and the old value is dropped
+pub(crate) fn rename(value: &mut Value, old: &str, new: &str) -> bool {
+    let item = match remove(value, &old.split('.').collect::<Vec<_>>()) {
+        Some(item) => item,
+        None => return false,
+    };
+    let new = new.split('.').collect::<Vec<_>>();
+    if new
+        .iter()
+        .try_fold(&*value, |value, part| value.get(*part))
+        .is_none()
+    {
+        insert(value, &new, item);
+    }
+    true
+}
+
+/// Sets the value at the `path` to `s`, parsed as the type of the value which is already there
+///
+/// Keys match the `path` ignoring case. Arrays are comma-separated. If `guess` is set,
+/// a missing value (or an element of an empty array) is parsed as a bool or a number
+/// if it looks like one, otherwise it is a string
+///
+/// Returns the dotted path of the value, with the keys which were matched
+pub(crate) fn set_parsed(
+    value: &mut Value,
+    path: &[String],
+    s: &str,
+    guess: bool,
+) -> Result<String, String> {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return Ok(String::new()),
+    };
+    let mut keys = vec![];
+    let mut value = value;
+    for part in parents {
+        let table = match value {
+            Value::Table(table) => table,
+            _ => return Err(format!("`{}` is not a table", part)),
+        };
+        let key = find_key(table, part);
+        keys.push(key.clone());
+        value = table
+            .entry(key)
+            .or_insert_with(|| Value::Table(Table::new()));
+    }
+    let table = match value {
+        Value::Table(table) => table,
+        _ => return Err(format!("`{}` is not a table", last)),
+    };
+    let key = find_key(table, last);
+    let item = parse_as(table.get(&key), s, guess)?;
+    keys.push(key.clone());
+    table.insert(key, item);
+    Ok(keys.join("."))
+}
+
+/// The key in `table` which is `part` ignoring case, or `part` if there isn't one
+fn find_key(table: &Table, part: &str) -> String {
+    table
+        .keys()
+        .find(|key| key.eq_ignore_ascii_case(part))
+        .cloned()
+        .unwrap_or_else(|| part.to_string())
+}
+
+/// Parses `s` as the same type as `old`, see `set_parsed`
+fn parse_as(old: Option<&Value>, s: &str, guess: bool) -> Result<Value, String> {
+    let s = s.trim();
+    let value = match old {
+        Some(Value::String(..)) => Value::String(s.to_string()),
+        Some(Value::Integer(..)) => Value::Integer(s.parse().map_err(|err| format!("{}", err))?),
+        Some(Value::Float(..)) => Value::Float(s.parse().map_err(|err| format!("{}", err))?),
+        Some(Value::Boolean(..)) => Value::Boolean(crate::env::parse_bool(s)?),
+        Some(Value::Datetime(..)) => Value::Datetime(s.parse().map_err(|err| format!("{}", err))?),
+        Some(Value::Array(items)) => Value::Array(
+            s.split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| parse_as(items.first(), s, guess))
+                .collect::<Result<_, _>>()?,
+        ),
+        Some(Value::Table(..)) => return Err("expected a table, set its fields instead".into()),
+        None if !guess => Value::String(s.to_string()),
+        None => match crate::env::parse_bool(s) {
+            Ok(b) if !s.bytes().all(|b| b.is_ascii_digit()) => Value::Boolean(b),
+            _ => s
+                .parse()
+                .map(Value::Integer)
+                .or_else(|_| s.parse().map(Value::Float))
+                .unwrap_or_else(|_| Value::String(s.to_string())),
+        },
+    };
+    Ok(value)
+}
+
+/// Replaces `${VAR}` in every string in `value` with the variable from `vars`
+///
+/// `$$` is a literal `$`. A missing variable is an error, with its name
+pub(crate) fn expand_vars(value: &mut Value, vars: &HashMap<String, String>) -> Result<(), String> {
+    match value {
+        Value::String(s) if s.contains('$') => *s = expand(s, vars)?,
+        Value::Array(items) => {
+            for item in items {
+                expand_vars(item, vars)?
+            }
+        }
+        Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                expand_vars(item, vars)?
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand(s: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if let Some(tail) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = tail;
+            continue;
+        }
+        let name = rest
+            .strip_prefix("${")
+            .and_then(|tail| tail.split_once('}'))
+            .map(|(name, _)| name);
+        match name {
+            Some(name) => {
+                let var = vars.get(name).ok_or_else(|| name.to_string())?;
+                out.push_str(var);
+                rest = &rest[name.len() + 3..];
+            }
+            None => {
+                out.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
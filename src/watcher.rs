@@ -0,0 +1,162 @@
+use super::*;
+
+#[cfg(feature = "notify")]
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Watches the configuration file for changes, e.g. saves from other processes
+///
+/// Every file which is loaded is watched (like `LoadToken`, e.g. the files it
+/// `EXTENDS`), and they are checked every `interval`. With the `notify` feature the platform's
+/// file notifications are also used, so changes are seen sooner. Each change
+/// yields the newly loaded instance (or why it couldn't be loaded)
+///
+/// See `Configurable::watch`
+///
+/// ```no_run
+/// # use configurable::{Configurable, Shared};
+/// # use std::time::Duration;
+/// # fn sync<T: Configurable + Send + Sync + 'static>(shared: Shared<T>) {
+/// let watcher = T::watch(Duration::from_secs(1)).unwrap();
+/// for change in watcher {
+///     match change {
+///         Ok(this) => shared.set(this),
+///         Err(err) => eprintln!("cannot reload: {}", err),
+///     }
+/// }
+/// # }
+/// ```
+pub struct Watcher<T> {
+    receiver: mpsc::Receiver<Result<T, Error>>,
+    wake: mpsc::Sender<Wake>,
+    handle: Option<JoinHandle<()>>,
+}
+
+enum Wake {
+    #[cfg_attr(not(feature = "notify"), allow(dead_code))]
+    Check,
+    Stop,
+}
+
+impl<T: Configurable + Send + 'static> Watcher<T> {
+    /// Starts watching the file for `T`, checking it every `interval`
+    pub fn new(interval: Duration) -> Result<Self, Error> {
+        // so a missing home directory is an error here, rather than for every check
+        T::try_path()?;
+        let (sender, receiver) = mpsc::channel();
+        let (wake, wakes) = mpsc::channel();
+
+        // the files which are read depend on what was loaded
+        let (_, mut token) = LoadToken::record(T::load);
+        #[cfg(feature = "notify")]
+        let (mut _notifier, rewake) = (notifier(&token, wake.clone()), wake.clone());
+
+        let handle = std::thread::spawn(move || loop {
+            match wakes.recv_timeout(interval) {
+                Ok(Wake::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(Wake::Check) | Err(RecvTimeoutError::Timeout) => {}
+            }
+            if token.is_current() {
+                continue;
+            }
+            let (this, next) = LoadToken::record(T::load);
+            #[cfg(feature = "notify")]
+            {
+                if !next.paths().eq(token.paths()) {
+                    _notifier = notifier(&next, rewake.clone());
+                }
+            }
+            token = next;
+            if sender.send(this).is_err() {
+                break;
+            }
+        });
+
+        Ok(Self {
+            receiver,
+            wake,
+            handle: Some(handle),
+        })
+    }
+
+    /// Waits for the next change
+    pub fn recv(&self) -> Option<Result<T, Error>> {
+        self.receiver.recv().ok()
+    }
+
+    /// Returns the next change, if there is one
+    pub fn try_recv(&self) -> Option<Result<T, Error>> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next change, up to `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<T, Error>> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl<T: Configurable + Send + 'static> Iterator for Watcher<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl<T> Drop for Watcher<T> {
+    fn drop(&mut self) {
+        let _ = self.wake.send(Wake::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Watches the directories of the files of `token`, waking the thread when they change
+///
+/// Returns `None` if the platform's notifications aren't available, then only
+/// the interval is used
+#[cfg(feature = "notify")]
+fn notifier(token: &LoadToken, wake: mpsc::Sender<Wake>) -> Option<::notify::RecommendedWatcher> {
+    use ::notify::Watcher as _;
+
+    let targets = token.paths().map(Path::to_owned).collect::<Vec<_>>();
+    // a directory which was read (e.g. of the `FRAGMENTS`) is watched for its files
+    let mut dirs = targets
+        .iter()
+        .filter_map(|path| match path.is_dir() {
+            true => Some(path.clone()),
+            false => path.parent().map(Path::to_owned),
+        })
+        .filter(|dir| dir.exists())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.dedup();
+    if dirs.is_empty() {
+        return None;
+    }
+
+    let mut watcher =
+        ::notify::recommended_watcher(move |ev: ::notify::Result<::notify::Event>| {
+            let changed = |path: &PathBuf| {
+                targets.contains(path)
+                    || path
+                        .parent()
+                        .is_some_and(|dir| targets.iter().any(|t| t == dir))
+            };
+            if let Ok(ev) = ev {
+                if ev.paths.iter().any(changed) {
+                    let _ = wake.send(Wake::Check);
+                }
+            }
+        })
+        .ok()?;
+    for dir in dirs {
+        watcher
+            .watch(&dir, ::notify::RecursiveMode::NonRecursive)
+            .ok()?;
+    }
+    Some(watcher)
+}